@@ -0,0 +1,346 @@
+/*
+** src/bin/intcode.rs
+** interactive runner for Intcode programs
+*/
+
+use std::env;
+use std::fs::File;
+use std::io::{self, prelude::*};
+use std::ops::Range;
+use std::process;
+
+use aoc2019::types::intcode::{
+    self, analysis::Disassembly, ascii, Intcode, RunDiff, Severity, Status, Transcript,
+};
+
+const USAGE: &str = "\
+usage: intcode [options] <program>
+       intcode [options] --load <state>
+
+options:
+    --ascii          exchange input and output as ASCII text
+    --protect        fault if the program writes to its code or executes
+                     outside of it, as found by static analysis
+    --save <state>   save the paused program to <state> when input runs out
+    --load <state>   resume a program previously saved with --save
+    --record <file>  record every input and output to a transcript
+    --replay <file>  replay a transcript, reporting the first divergence
+    --emit-rust      print the program translated into a Rust module
+    --disassemble    print a listing of the program's reachable code
+    --decompile      print the program lifted into structured pseudo-code
+    --diff <a> <b>   run the program with each of two comma-separated inputs
+                     and report how the runs differ
+    --optimize       print the program after peephole optimization, verified
+                     against the --replay transcript if one is given
+    --validate       check the program's reachable code without running it
+    --dump <range>   print memory in <range>, such as 0..64 or 100.., when the
+                     program stops, marking the instruction pointer and cells
+                     modified since it was loaded";
+
+#[derive(Default)]
+struct Options {
+    help: bool,
+    ascii: bool,
+    protect: bool,
+    path: Option<String>,
+    save: Option<String>,
+    load: Option<String>,
+    record: Option<String>,
+    replay: Option<String>,
+    emit_rust: bool,
+    disassemble: bool,
+    decompile: bool,
+    optimize: bool,
+    validate: bool,
+    diff: Option<(String, String)>,
+    dump: Option<Range<usize>>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item=String>) -> Result<Self, String> {
+        let mut opts = Self::default();
+
+        while let Some(arg) = args.next() {
+            // the value following an option
+            let mut value = || args.next()
+                .ok_or_else(|| format!("{} requires a value\n{}", arg, USAGE));
+
+            match arg.as_str() {
+                "--ascii"  => opts.ascii = true,
+                "--protect" => opts.protect = true,
+                "--emit-rust" => opts.emit_rust = true,
+                "--disassemble" => opts.disassemble = true,
+                "--decompile" => opts.decompile = true,
+                "--optimize" => opts.optimize = true,
+                "--validate" => opts.validate = true,
+                "--diff"   => opts.diff = Some((value()?, value()?)),
+                "--save"   => opts.save = Some(value()?),
+                "--load"   => opts.load = Some(value()?),
+                "--record" => opts.record = Some(value()?),
+                "--replay" => opts.replay = Some(value()?),
+                "--dump"   => opts.dump = Some(parse_range(&value()?)?),
+                "-h" | "--help" => return Ok(Self { help: true, ..Self::default() }),
+                s if s.starts_with('-') => {
+                    return Err(format!("unknown option {}\n{}", s, USAGE))
+                },
+                _ if opts.path.is_some() => {
+                    return Err(format!("unexpected argument {}\n{}", arg, USAGE))
+                },
+                _ => opts.path = Some(arg),
+            }
+        }
+
+        // exactly one of a program or a saved state is required
+        if opts.path.is_some() == opts.load.is_some() {
+            return Err(USAGE.to_owned());
+        }
+        if opts.diff.is_some() && opts.load.is_some() {
+            return Err(format!("--diff requires a program rather than a saved state\n{}", USAGE));
+        }
+
+        Ok(opts)
+    }
+}
+
+// prints all pending program output; in ASCII mode, character codes are
+// printed as text and any other values are printed as integers
+fn flush_output(prog: &mut Intcode, ascii: bool) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    while let Some(out) = prog.output() {
+        match ascii::decode(out) {
+            Some(c) if ascii => write!(stdout, "{}", c)?,
+            _ => writeln!(stdout, "{}", out)?,
+        }
+    }
+
+    stdout.flush()
+}
+
+// reads the next line of input into the program, returns false on EOF
+fn read_input(prog: &mut Intcode, ascii: bool) -> Result<bool, String> {
+    let mut line = String::new();
+
+    loop {
+        if !ascii {
+            eprint!("? ");
+        }
+
+        line.clear();
+        let n = io::stdin().read_line(&mut line).map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Ok(false);
+        }
+
+        if ascii {
+            match ascii::encode_line(&line) {
+                Ok(vals) => {
+                    for val in vals {
+                        prog.input(val);
+                    }
+                    return Ok(true);
+                },
+                Err(err) => {
+                    eprintln!("{}", err);
+                    continue;
+                },
+            }
+        }
+
+        // integer mode accepts any number of whitespace/comma-separated values
+        let tokens = line.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|tok| !tok.is_empty())
+            .map(|tok| tok.parse::<i64>().map_err(|_| tok.to_owned()))
+            .collect::<Result<Vec<_>, _>>();
+
+        match tokens {
+            Ok(ref vals) if vals.is_empty() => continue,
+            Ok(vals) => {
+                for val in vals {
+                    prog.input(val);
+                }
+                return Ok(true);
+            },
+            Err(tok) => eprintln!("invalid integer {:?}", tok),
+        }
+    }
+}
+
+fn load(opts: &Options) -> Result<Intcode, String> {
+    if let Some(ref path) = opts.load {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        Intcode::load_state(file).map_err(|e| format!("{}: {}", path, e))
+    } else {
+        let path = opts.path.as_ref().unwrap();
+        let memory = Intcode::read_program_file(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Intcode::new(memory))
+    }
+}
+
+fn save(prog: &Intcode, path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    prog.save_state(file).map_err(|e| format!("{}: {}", path, e))?;
+    eprintln!("saved program state to {}", path);
+    Ok(())
+}
+
+// runs an interactive session until the program halts or input runs out
+fn session(prog: &mut Intcode, opts: &Options) -> Result<(), String> {
+    loop {
+        prog.run();
+        flush_output(prog, opts.ascii).map_err(|e| e.to_string())?;
+
+        match prog.status {
+            Status::Waiting => {
+                if !read_input(prog, opts.ascii)? {
+                    // the session can be resumed later if a save file is given
+                    return match opts.save {
+                        Some(ref path) => save(prog, path),
+                        None => Err("end of input while program is waiting".to_owned()),
+                    };
+                }
+            },
+            Status::Halted => return Ok(()),
+            Status::Faulted(ref fault) => return Err(fault.to_string()),
+            Status::Initial | Status::Running => unreachable!(),
+        }
+    }
+}
+
+fn replay(prog: &mut Intcode, path: &str) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let transcript = Transcript::read(file).map_err(|e| format!("{}: {}", path, e))?;
+
+    transcript.replay(prog).map_err(|div| format!("replay diverged at {}", div))?;
+    eprintln!("replay matched all {} events", transcript.events().len());
+    Ok(())
+}
+
+fn optimize(prog: &Intcode, opts: &Options) -> Result<(), String> {
+    let mut transcripts = vec![];
+    if let Some(ref path) = opts.replay {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        transcripts.push(Transcript::read(file).map_err(|e| format!("{}: {}", path, e))?);
+    }
+
+    let opt = intcode::optimize(&prog.memory, &transcripts)
+        .map_err(|e| format!("cannot optimize program: {}", e))?;
+    let text = opt.memory.iter().map(|val| val.to_string()).collect::<Vec<_>>();
+    println!("{}", text.join(","));
+    eprintln!(
+        "folded {} constant instructions, rewrote {} moves, removed {} unreachable cells",
+        opt.folded, opt.moves, opt.removed);
+    Ok(())
+}
+
+fn validate(prog: &Intcode) -> Result<(), String> {
+    let diags = prog.validate();
+    for diag in diags.iter() {
+        println!("{}", diag);
+    }
+
+    let errors = diags.iter().filter(|diag| diag.severity() == Severity::Error).count();
+    match errors {
+        0 => Ok(()),
+        1 => Err("1 error found".to_owned()),
+        n => Err(format!("{} errors found", n)),
+    }
+}
+
+// parses a range of addresses such as 10..20; either end may be omitted
+fn parse_range(text: &str) -> Result<Range<usize>, String> {
+    let invalid = || format!("invalid range {:?}", text);
+    let (start, end) = text.split_once("..").ok_or_else(invalid)?;
+    let start = match start {
+        "" => 0,
+        s => s.parse().map_err(|_| invalid())?,
+    };
+    let end = match end {
+        "" => usize::MAX,
+        s => s.parse().map_err(|_| invalid())?,
+    };
+    Ok(start..end)
+}
+
+// parses a comma-separated list of inputs given on the command line
+fn parse_inputs(text: &str) -> Result<Vec<i64>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|tok| !tok.is_empty())
+        .map(|tok| tok.parse::<i64>().map_err(|_| format!("invalid input {:?}", tok)))
+        .collect()
+}
+
+fn run(opts: &Options) -> Result<(), String> {
+    let mut prog = load(opts)?;
+
+    if opts.emit_rust {
+        let source = intcode::transpile(&prog.memory)
+            .map_err(|e| format!("cannot translate program: {}", e))?;
+        print!("{}", source);
+        return Ok(());
+    }
+    if opts.disassemble {
+        let disasm = Disassembly::explore(&prog.memory, prog.opcodes(), |d| d.errors.is_empty());
+        print!("{}", disasm);
+        return Ok(());
+    }
+    if opts.decompile {
+        print!("{}", intcode::decompile(&prog.memory));
+        return Ok(());
+    }
+    if let Some((ref left, ref right)) = opts.diff {
+        let diff = RunDiff::from_inputs(&prog.memory, &parse_inputs(left)?, &parse_inputs(right)?);
+        print!("{}", diff);
+        return Ok(());
+    }
+    if opts.optimize {
+        return optimize(&prog, opts);
+    }
+    if opts.validate {
+        return validate(&prog);
+    }
+    if let Some(ref path) = opts.replay {
+        return replay(&mut prog, path);
+    }
+    if opts.protect {
+        prog = prog.with_code_protection();
+    }
+    if opts.record.is_some() {
+        prog = prog.with_recording();
+    }
+
+    let res = session(&mut prog, opts);
+
+    // the transcript is kept even if the session ended in an error, since
+    // that is when it is most useful
+    if let (Some(path), Some(transcript)) = (&opts.record, prog.transcript()) {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        transcript.write(file).map_err(|e| format!("{}: {}", path, e))?;
+        eprintln!("recorded {} events to {}", transcript.events().len(), path);
+    }
+    if let Some(ref range) = opts.dump {
+        eprint!("{}", prog.dump(range.clone()));
+    }
+
+    res
+}
+
+fn main() {
+    let opts = match Options::parse(env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(2);
+        },
+    };
+    if opts.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Err(msg) = run(&opts) {
+        eprintln!("error: {}", msg);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,10 @@
+/*
+** src/lib.rs
+** Advent of Code 2019
+** as implemented in Rust by Ian Brault
+** see https://adventofcode.com/2019
+*/
+
+pub mod puzzles;
+pub mod types;
+pub mod utils;
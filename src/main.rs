@@ -5,9 +5,7 @@
 ** see https://adventofcode.com/2019
 */
 
-mod puzzles;
-mod types;
-mod utils;
+use aoc2019::puzzles;
 
 fn main() {
     println!("Advent of Code 2019 [Rust]");
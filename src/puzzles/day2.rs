@@ -52,7 +52,7 @@ impl Puzzle for Day2 {
                 }
 
                 if prog.memory[0] == 19_690_720 {
-                    return (100 * noun + verb) as i64;
+                    return 100 * noun + verb;
                 } else if prog.memory[0] < 19_690_720 {
                     // the Intcode program is monotonically increasing
                     continue 'noun_loop;
@@ -28,7 +28,7 @@ impl Puzzle for Day4 {
     /// (3) two adjacent digits are the same (like 22 in 122345)
     /// (4) from left to right, the digits never decrease; they only ever
     ///     increase or stay the same (like 111123 or 135679)
-
+    ///
     /// How many different passwords within the range given in your puzzle
     /// input meet these criteria?
     fn part_1(&self) -> i64 {
@@ -46,7 +46,7 @@ impl Puzzle for Day4 {
     /// larger group of 444)
     /// ex: 111122 meets the criteria (even though 1 is repeated more than
     /// twice, it still contains a double 22)
-
+    ///
     /// How many different passwords within the range given in your puzzle
     /// input meet all of the criteria?
     fn part_2(&self) -> i64 {
@@ -74,8 +74,7 @@ impl Puzzle for Day6 {
 
         // continue this process for the objects which orbit the COM and the
         // objects which orbit them until all objects have been processed
-        while !queue.is_empty() {
-            let (center, orbit) = queue.pop().unwrap();
+        while let Some((center, orbit)) = queue.pop() {
             orbit_cnts.insert(orbit.clone(), orbit_cnts[&center] + 1);
 
             if self.orbit_map.contains_key(&orbit) {
@@ -99,8 +98,7 @@ impl Puzzle for Day6 {
 
         // continue this process for the objects which orbit the COM and the
         // objects which orbit them until all objects have been processed
-        while !queue.is_empty() {
-            let (center, orbit) = queue.pop().unwrap();
+        while let Some((center, orbit)) = queue.pop() {
             dag.insert(orbit.clone(), center);
 
             if self.orbit_map.contains_key(&orbit) {
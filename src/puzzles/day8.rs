@@ -26,7 +26,7 @@ impl Day8 {
 
     fn display_image(layer: Vec<u8>) {
         let width = 25;  // from input
-        for row in utils::clump(layer.into_iter(), width) {
+        for row in utils::clump(layer, width) {
             print!("█");
             for pixel in row {
                 if pixel == 0 {
@@ -20,6 +20,7 @@ impl<T> DAGNode<T> {
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct DAG<T>
 where T: Clone + Hash + Eq
 {
@@ -56,7 +57,7 @@ where T: Clone + Hash + Eq
     }
 
     // NOTE: does not include item in the path
-    pub fn path_to_root<'a>(&'a self, item: T) -> Vec<&'a T> {
+    pub fn path_to_root(&self, item: T) -> Vec<&T> {
         let mut path = vec![];
 
         // get the item node
@@ -75,3 +76,11 @@ where T: Clone + Hash + Eq
         path
     }
 }
+
+impl<T> Default for DAG<T>
+where T: Clone + Hash + Eq
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
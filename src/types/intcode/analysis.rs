@@ -0,0 +1,502 @@
+/*
+** src/types/intcode/analysis.rs
+** static disassembly and control flow of Intcode programs
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::types::intcode::{Instr, OpcodeTable, Param, ParameterMode};
+
+/// A single parameter of a decoded instruction. A patched operand is one the
+/// program overwrites, so `value` is only its initial value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Operand {
+    pub kind: Param,
+    pub mode: ParameterMode,
+    pub value: i64,
+    pub patched: bool,
+}
+
+impl Operand {
+    /// the address read from, for position-mode read parameters
+    pub fn read_addr(&self) -> Option<i64> {
+        match (self.kind, self.mode, self.patched) {
+            (Param::Read, ParameterMode::Position, false) => Some(self.value),
+            _ => None,
+        }
+    }
+
+    /// the address written to, for write parameters; the VM uses write
+    /// parameters as addresses regardless of their mode
+    pub fn write_addr(&self) -> Option<i64> {
+        match (self.kind, self.patched) {
+            (Param::Write, false) => Some(self.value),
+            _ => None,
+        }
+    }
+
+    /// the constant value of an immediate-mode read parameter
+    pub fn immediate(&self) -> Option<i64> {
+        match (self.kind, self.mode, self.patched) {
+            (Param::Read, ParameterMode::Immediate, false) => Some(self.value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // patched operands are marked, since their value may change
+        let mark = if self.patched { "'" } else { "" };
+        match (self.kind, self.mode) {
+            (Param::Read, ParameterMode::Immediate) => write!(f, "{}{}", self.value, mark),
+            _ => write!(f, "[{}{}]", self.value, mark),
+        }
+    }
+}
+
+/// An instruction decoded at a known address
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub opcode: i64,
+    pub name: &'static str,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    /// the number of memory cells occupied by the instruction
+    pub fn size(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    /// the address of the cell following the instruction
+    pub fn next_addr(&self) -> usize {
+        self.addr + self.size()
+    }
+
+    pub fn is_jump(&self) -> bool {
+        self.opcode == 5 || self.opcode == 6
+    }
+
+    // for a jump with a constant condition, whether or not it always jumps
+    fn constant_jump(&self) -> Option<bool> {
+        self.operands[0].immediate().map(|cond| (cond != 0) == (self.opcode == 5))
+    }
+
+    /// the target of a jump, if the jump can ever be taken
+    pub fn jump_target(&self) -> Option<Target> {
+        if !self.is_jump() || self.constant_jump() == Some(false) {
+            return None;
+        }
+        Some(match self.operands[1].immediate() {
+            Some(addr) => Target::Static(addr as usize),
+            None => Target::Dynamic,
+        })
+    }
+
+    /// whether or not control can continue to the following instruction
+    pub fn falls_through(&self) -> bool {
+        match self.opcode {
+            99 => false,
+            5 | 6 => self.constant_jump() != Some(true),
+            _ => true,
+        }
+    }
+
+    /// Where control may go after the instruction, for the standard opcodes.
+    /// Halts have no successors, and jumps to a computed address are
+    /// reported as `Target::Dynamic`.
+    pub fn successors(&self) -> Vec<Target> {
+        let mut succ = vec![];
+        if self.falls_through() {
+            succ.push(Target::Static(self.next_addr()));
+        }
+        if let Some(target) = self.jump_target() {
+            succ.push(target);
+        }
+        succ
+    }
+
+    /// the cells occupied by the instruction
+    pub fn cells(&self) -> std::ops::Range<usize> {
+        self.addr..self.next_addr()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (n, op) in self.operands.iter().enumerate() {
+            let sep = if n == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, op)?;
+        }
+        Ok(())
+    }
+}
+
+/// A possible destination of control flow
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Target {
+    Static(usize),  // a known address
+    Dynamic,        // an address computed at runtime
+}
+
+/// Reasons an instruction could not be decoded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodeError {
+    /// control reaches an address outside of memory
+    OutOfBounds,
+    /// the instruction has a parameter mode which is not 0 or 1
+    InvalidMode(i64),
+    /// the opcode is not defined in the opcode table
+    UnknownOpcode(i64),
+    /// the instruction's parameters extend past the end of memory
+    Truncated,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::OutOfBounds => write!(f, "address is outside of memory"),
+            DecodeError::InvalidMode(n) => write!(f, "invalid parameter mode in {}", n),
+            DecodeError::UnknownOpcode(op) => write!(f, "unknown opcode {}", op),
+            DecodeError::Truncated => write!(f, "instruction extends past the end of memory"),
+        }
+    }
+}
+
+/// decodes the instruction at an address
+pub fn decode(memory: &[i64], addr: usize, opcodes: &OpcodeTable) -> Result<Instruction, DecodeError> {
+    let n = *memory.get(addr).ok_or(DecodeError::OutOfBounds)?;
+    let instr = Instr::decode(n).ok_or(DecodeError::InvalidMode(n))?;
+    let op = opcodes.get(instr.opcode).ok_or(DecodeError::UnknownOpcode(instr.opcode))?;
+
+    if addr + op.arity() >= memory.len() {
+        return Err(DecodeError::Truncated);
+    }
+
+    let operands = op.params.iter()
+        .enumerate()
+        .map(|(pn, &kind)| Operand {
+            kind,
+            mode: instr.param_modes[pn],
+            value: memory[addr + pn + 1],
+            patched: false,
+        })
+        .collect();
+
+    Ok(Instruction { addr, opcode: instr.opcode, name: op.name, operands })
+}
+
+// Where control may continue after an undecodable word which the program
+// writes before it runs. The word is decoded as each defined opcode with the
+// same parameter modes, and a guess is kept only if the code it continues at
+// can also be decoded.
+fn continuations(memory: &[i64], addr: usize, opcodes: &OpcodeTable) -> BTreeSet<usize> {
+    let mut memory = memory.to_vec();
+    let modes = match memory.get(addr) {
+        Some(&word) => word - word % 100,
+        None => return BTreeSet::new(),
+    };
+
+    let mut next = BTreeSet::new();
+    for (code, _) in opcodes.iter() {
+        memory[addr] = modes + code;
+        let instr = match decode(&memory, addr, opcodes) {
+            Ok(instr) => instr,
+            Err(_) => continue,
+        };
+        let targets = instr.successors()
+            .into_iter()
+            .filter_map(|target| match target {
+                Target::Static(addr) => Some(addr),
+                Target::Dynamic => None,
+            })
+            .collect::<Vec<_>>();
+        if targets.iter().all(|&target| decode(&memory, target, opcodes).is_ok()) {
+            next.extend(targets);
+        }
+    }
+    next
+}
+
+/// The instructions reachable by following control flow from a set of entry
+/// points, usually just address 0
+pub struct Disassembly {
+    pub instrs: BTreeMap<usize, Instruction>,
+    /// addresses reached by control flow which could not be decoded
+    pub errors: BTreeMap<usize, DecodeError>,
+    /// addresses of jumps whose target is computed at runtime
+    pub dynamic_jumps: BTreeSet<usize>,
+    /// addresses which are the target of a static jump
+    pub jump_targets: BTreeSet<usize>,
+    /// operand cells which the program writes to, so that their values are
+    /// only known at runtime
+    pub patched: BTreeSet<usize>,
+    /// the entry points control flow was walked from
+    pub roots: Vec<usize>,
+    /// undecodable words which the program writes before they run, with the
+    /// addresses at which control may continue after them
+    pub rewritten: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl Disassembly {
+    /// Disassembles a program by walking its control flow from address 0.
+    /// Jumps to computed addresses cannot be followed, so code reached only
+    /// through them is not included.
+    pub fn new(memory: &[i64], opcodes: &OpcodeTable) -> Self {
+        Self::with_roots(memory, opcodes, &[0])
+    }
+
+    /// Disassembles a program by walking its control flow from each of the
+    /// given entry points. Operands which the reachable code writes to are
+    /// marked as patched, which can in turn make jumps dynamic, and words
+    /// which cannot be decoded until the reachable code writes them are walked
+    /// past, so the walk is repeated until both sets are stable.
+    pub fn with_roots(memory: &[i64], opcodes: &OpcodeTable, roots: &[usize]) -> Self {
+        let mut patched = BTreeSet::new();
+        let mut rewritten = BTreeSet::new();
+        loop {
+            let disasm = Self::walk(memory, opcodes, roots, patched, &rewritten);
+
+            let written = disasm.static_writes()
+                .map(|(_, target)| target)
+                .filter(|&target| disasm.is_code(target) && !disasm.instrs.contains_key(&target))
+                .collect::<BTreeSet<_>>();
+            let undecodable = disasm.static_writes()
+                .map(|(_, target)| target)
+                .filter(|target| disasm.errors.contains_key(target))
+                .collect::<BTreeSet<_>>();
+            if written.is_subset(&disasm.patched) && undecodable.is_subset(&rewritten) {
+                return disasm;
+            }
+
+            patched = disasm.patched.union(&written).cloned().collect();
+            rewritten.extend(undecodable);
+        }
+    }
+
+    /// Disassembles a program, guessing at code reached only through dynamic
+    /// jumps: any value in memory which is the address of a decodable
+    /// instruction is tried as an extra entry point, and kept if the result is
+    /// still accepted by `accept`. Wrong guesses cost only unused code, and
+    /// without dynamic jumps this is the same as `Disassembly::new`.
+    pub fn explore<F>(memory: &[i64], opcodes: &OpcodeTable, accept: F) -> Self
+        where F: Fn(&Disassembly) -> bool
+    {
+        let disasm = Self::new(memory, opcodes);
+        if disasm.dynamic_jumps.is_empty() || !accept(&disasm) {
+            return disasm;
+        }
+
+        let candidates = memory.iter()
+            .filter(|&&val| val > 0 && (val as usize) < memory.len())
+            .map(|&val| val as usize)
+            .filter(|addr| !disasm.instrs.contains_key(addr))
+            .filter(|&addr| decode(memory, addr, opcodes).is_ok())
+            .collect::<BTreeSet<_>>();
+
+        let mut roots = vec![0];
+        for addr in candidates {
+            if accept(&Self::with_roots(memory, opcodes, &[0, addr])) {
+                roots.push(addr);
+            }
+        }
+
+        let combined = Self::with_roots(memory, opcodes, &roots);
+        if accept(&combined) { combined } else { disasm }
+    }
+
+    fn walk(
+        memory: &[i64], opcodes: &OpcodeTable, roots: &[usize],
+        patched: BTreeSet<usize>, rewritten: &BTreeSet<usize>,
+    ) -> Self {
+        let mut instrs = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+        let mut continues = BTreeMap::new();
+        let mut dynamic_jumps = BTreeSet::new();
+        let mut jump_targets = BTreeSet::new();
+
+        let mut queue = roots.to_vec();
+        let mut seen = BTreeSet::new();
+
+        while let Some(addr) = queue.pop() {
+            if !seen.insert(addr) {
+                continue;
+            }
+
+            let mut instr = match decode(memory, addr, opcodes) {
+                Ok(instr) => instr,
+                Err(err) => {
+                    if rewritten.contains(&addr) {
+                        let next = continuations(memory, addr, opcodes);
+                        queue.extend(next.iter().cloned());
+                        continues.insert(addr, next);
+                    }
+                    errors.insert(addr, err);
+                    continue;
+                },
+            };
+            for (pn, op) in instr.operands.iter_mut().enumerate() {
+                op.patched = patched.contains(&(addr + pn + 1));
+            }
+
+            match instr.jump_target() {
+                Some(Target::Static(target)) => {
+                    jump_targets.insert(target);
+                },
+                Some(Target::Dynamic) => {
+                    dynamic_jumps.insert(addr);
+                },
+                None => {},
+            }
+            for target in instr.successors() {
+                if let Target::Static(next) = target {
+                    queue.push(next);
+                }
+            }
+
+            instrs.insert(addr, instr);
+        }
+
+        Self {
+            instrs,
+            errors,
+            dynamic_jumps,
+            jump_targets,
+            patched,
+            roots: roots.to_vec(),
+            rewritten: continues,
+        }
+    }
+
+    /// the instruction occupying a cell, if the cell is part of reachable code
+    pub fn instr_at(&self, addr: usize) -> Option<&Instruction> {
+        self.instrs.range(..=addr)
+            .rev()
+            .take(4)
+            .map(|(_, instr)| instr)
+            .find(|instr| instr.cells().contains(&addr))
+    }
+
+    /// whether or not a cell is part of reachable code
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.instr_at(addr).is_some()
+    }
+
+    /// Addresses which start a basic block: the entry point, jump targets, and
+    /// instructions following a jump. If any jump is dynamic, every
+    /// instruction is a possible block start.
+    pub fn leaders(&self) -> BTreeSet<usize> {
+        if !self.dynamic_jumps.is_empty() {
+            return self.instrs.keys().cloned().collect();
+        }
+
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        leaders.extend(self.jump_targets.iter().cloned());
+        for instr in self.instrs.values().filter(|instr| instr.is_jump()) {
+            leaders.insert(instr.next_addr());
+        }
+
+        leaders.into_iter().filter(|addr| self.instrs.contains_key(addr)).collect()
+    }
+
+    /// writes to fixed addresses, as pairs of the instruction address and the
+    /// target address
+    pub fn static_writes(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.instrs.values().flat_map(|instr| {
+            instr.operands.iter()
+                .filter_map(|op| op.write_addr())
+                .filter(|&target| target >= 0)
+                .map(move |target| (instr.addr, target as usize))
+        })
+    }
+
+    /// Writes which replace the instruction word of reachable code, changing
+    /// the instruction itself, as pairs of the writing instruction address and
+    /// the target address. This includes words which cannot be decoded until
+    /// they are written. Writes to operands are tracked as patched instead.
+    pub fn opcode_writes(&self) -> Vec<(usize, usize)> {
+        self.static_writes()
+            .filter(|(_, target)| self.instrs.contains_key(target) || self.errors.contains_key(target))
+            .collect()
+    }
+
+    /// Writes to instruction words which may change the code that runs. A
+    /// write is harmless if the overwritten instruction can never execute
+    /// after it, such as a program storing its result over its first
+    /// instruction before halting.
+    pub fn live_opcode_writes(&self) -> Vec<(usize, usize)> {
+        self.opcode_writes()
+            .into_iter()
+            .filter(|&(instr, target)| match self.reachable_from(instr) {
+                Some(reachable) => reachable.contains(&target),
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Addresses of the instructions, and undecodable words, which control may
+    /// reach after the instruction at `addr`, or None if a dynamic jump may be
+    /// reached so that any instruction may execute.
+    pub fn reachable_from(&self, addr: usize) -> Option<BTreeSet<usize>> {
+        let mut seen = BTreeSet::new();
+        let mut queue = self.instrs.get(&addr)?.successors();
+
+        while let Some(target) = queue.pop() {
+            match target {
+                Target::Dynamic => return None,
+                Target::Static(next) => match self.instrs.get(&next) {
+                    Some(instr) if seen.insert(next) => queue.extend(instr.successors()),
+                    Some(_) => {},
+                    // control stops at an undecodable word, unless it is
+                    // written before it runs
+                    None if self.errors.contains_key(&next) && seen.insert(next) => {
+                        let after = self.rewritten.get(&next).into_iter().flatten();
+                        queue.extend(after.map(|&addr| Target::Static(addr)));
+                    },
+                    None => {},
+                },
+            }
+        }
+        Some(seen)
+    }
+
+    /// addresses of instructions whose write target is itself patched, so
+    /// that the address written to is only known at runtime
+    pub fn dynamic_writes(&self) -> Vec<usize> {
+        self.instrs.values()
+            .filter(|instr| instr.operands.iter().any(|op| op.kind == Param::Write && op.patched))
+            .map(|instr| instr.addr)
+            .collect()
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut addrs = self.instrs.keys().chain(self.errors.keys()).collect::<Vec<_>>();
+        addrs.sort();
+
+        for addr in addrs {
+            if self.jump_targets.contains(addr) {
+                writeln!(f, "{:>5}:", format!("L{}", addr))?;
+            }
+            match (self.instrs.get(addr), self.errors.get(addr)) {
+                (Some(instr), _) => writeln!(f, "{:>5}  {}", addr, instr)?,
+                (_, Some(err)) => {
+                    write!(f, "{:>5}  ?? {}", addr, err)?;
+                    if let Some(after) = self.rewritten.get(addr) {
+                        let after = after.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                        write!(f, " (written at runtime; continues at {})", after.join(" or "))?;
+                    }
+                    writeln!(f)?;
+                },
+                (None, None) => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+/*
+** src/types/intcode/ascii.rs
+** an adapter for Intcode programs which communicate in ASCII text
+*/
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::types::intcode::{Intcode, Status};
+
+/// Text which cannot be sent to an ASCII program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AsciiError {
+    NonAscii(char),
+}
+
+impl fmt::Display for AsciiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsciiError::NonAscii(c) => write!(f, "non-ASCII character {:?} in input", c),
+        }
+    }
+}
+
+/// Encodes a command as character codes, followed by a newline to terminate
+/// it. Any line break at the end of the command is ignored.
+pub fn encode_line(line: &str) -> Result<Vec<i64>, AsciiError> {
+    line.trim_end_matches(&['\r', '\n'][..])
+        .chars()
+        .map(|c| match c.is_ascii() {
+            true => Ok(c as i64),
+            false => Err(AsciiError::NonAscii(c)),
+        })
+        .chain(Some(Ok(10)))
+        .collect()
+}
+
+/// the character an output value encodes, if it is ASCII
+pub fn decode(val: i64) -> Option<char> {
+    match val {
+        0..=127 => Some(val as u8 as char),
+        _ => None,
+    }
+}
+
+/// ASCII-art output, as rows of characters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    rows: Vec<Vec<char>>,
+}
+
+impl Grid {
+    /// builds a grid from lines of text, ignoring blank lines at the end
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> Self {
+        let mut rows = lines.iter()
+            .map(|line| line.as_ref().chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+        Self { rows }
+    }
+
+    /// the length of the longest row
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// the character at a column and row, if there is one
+    pub fn get(&self, x: usize, y: usize) -> Option<char> {
+        self.rows.get(y).and_then(|row| row.get(x)).cloned()
+    }
+
+    pub fn rows(&self) -> &[Vec<char>] {
+        &self.rows
+    }
+
+    /// the positions of a character, in reading order
+    pub fn find(&self, c: char) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.rows.iter()
+            .enumerate()
+            .flat_map(move |(y, row)| row.iter()
+                .enumerate()
+                .filter(move |&(_, &rc)| rc == c)
+                .map(move |(x, _)| (x, y)))
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows.iter() {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+/// Wraps a program which communicates in ASCII text. Commands are sent as
+/// lines of text, and output is collected into lines; values which are not
+/// character codes, such as a final answer, are collected separately.
+pub struct AsciiProgram {
+    prog: Intcode,
+    lines: VecDeque<String>,
+    // output following the last newline, such as a prompt
+    partial: String,
+    results: Vec<i64>,
+}
+
+impl AsciiProgram {
+    pub fn new(prog: Intcode) -> Self {
+        Self {
+            prog,
+            lines: VecDeque::new(),
+            partial: String::new(),
+            results: vec![],
+        }
+    }
+
+    /// sends a command to the program, as with `encode_line`
+    pub fn send_line(&mut self, line: &str) -> Result<(), AsciiError> {
+        for val in encode_line(line)? {
+            self.prog.input(val);
+        }
+        Ok(())
+    }
+
+    /// Runs the program until it halts, faults or waits for input, collecting
+    /// its output
+    pub fn run(&mut self) -> &Status {
+        self.prog.run();
+        while let Some(out) = self.prog.output() {
+            match decode(out) {
+                Some('\n') => self.lines.push_back(self.partial.split_off(0)),
+                Some(c) => self.partial.push(c),
+                None => self.results.push(out),
+            }
+        }
+        &self.prog.status
+    }
+
+    /// takes the next complete line of output
+    pub fn line(&mut self) -> Option<String> {
+        self.lines.pop_front()
+    }
+
+    /// takes every complete line of output
+    pub fn lines(&mut self) -> Vec<String> {
+        self.lines.drain(..).collect()
+    }
+
+    /// output which has not yet been terminated by a newline
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+
+    /// takes every complete line of output as a grid
+    pub fn grid(&mut self) -> Grid {
+        Grid::new(&self.lines())
+    }
+
+    /// the last value output which was not a character code
+    pub fn result(&self) -> Option<i64> {
+        self.results.last().cloned()
+    }
+
+    /// every value output which was not a character code, in order
+    pub fn results(&self) -> &[i64] {
+        &self.results
+    }
+
+    pub fn program(&self) -> &Intcode {
+        &self.prog
+    }
+
+    pub fn into_inner(self) -> Intcode {
+        self.prog
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::compile;
+
+    // prompts with "> ", echoes a line of input, then outputs the length of
+    // the line, which is not a character code
+    const ECHO: &str = "
+        output 62;
+        output 32;
+        n = 0;
+        input c;
+        while c != 10 {
+            output c;
+            n = n + 1;
+            input c;
+        }
+        output 10;
+        output n + 1000;
+    ";
+
+    fn program(source: &str) -> AsciiProgram {
+        AsciiProgram::new(Intcode::new(compile(source).unwrap()))
+    }
+
+    #[test]
+    fn encodes_lines() {
+        assert_eq!(encode_line("hi"), Ok(vec![104, 105, 10]));
+        assert_eq!(encode_line("hi\r\n"), Ok(vec![104, 105, 10]));
+        assert_eq!(encode_line(""), Ok(vec![10]));
+        let err = encode_line("café").unwrap_err();
+        assert_eq!(err, AsciiError::NonAscii('é'));
+        assert_eq!(err.to_string(), "non-ASCII character 'é' in input");
+
+        assert_eq!(decode(65), Some('A'));
+        assert_eq!(decode(128), None);
+        assert_eq!(decode(-1), None);
+    }
+
+    #[test]
+    fn exchanges_lines() {
+        let mut prog = program(ECHO);
+        assert_eq!(prog.run(), &Status::Waiting);
+        assert_eq!(prog.partial(), "> ");
+        assert_eq!(prog.line(), None);
+
+        prog.send_line("hello\n").unwrap();
+        assert_eq!(prog.run(), &Status::Halted);
+        assert_eq!(prog.lines(), vec!["> hello"]);
+        assert_eq!(prog.partial(), "");
+        assert_eq!(prog.result(), Some(1005));
+        assert_eq!(prog.results(), &[1005]);
+
+        // nothing is sent if the line cannot be encoded
+        let mut prog = program(ECHO);
+        assert!(prog.send_line("¡hola!").is_err());
+        prog.run();
+        assert_eq!(prog.partial(), "> ");
+        assert_eq!(prog.into_inner().status, Status::Waiting);
+    }
+
+    #[test]
+    fn grids() {
+        // outputs "#..\n.#\n\n"
+        let mut prog = program("
+            output 35; output 46; output 46; output 10;
+            output 46; output 35; output 10;
+            output 10;
+        ");
+        prog.run();
+        let grid = prog.grid();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(0, 0), Some('#'));
+        assert_eq!(grid.get(2, 1), None);
+        assert_eq!(grid.get(0, 2), None);
+        assert_eq!(grid.find('#').collect::<Vec<_>>(), vec![(0, 0), (1, 1)]);
+        assert_eq!(grid.to_string(), "#..\n.#\n");
+        assert_eq!(grid, Grid::new(&["#..", ".#"]));
+        assert_eq!(prog.line(), None);
+
+        assert_eq!(Grid::new::<&str>(&[]).width(), 0);
+    }
+}
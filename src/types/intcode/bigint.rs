@@ -0,0 +1,363 @@
+/*
+** src/types/intcode/bigint.rs
+** arbitrary-precision values for big-integer arithmetic
+*/
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Mul};
+use std::str::FromStr;
+
+use crate::types::intcode::{Instr, Intcode, ParameterMode, Protection};
+
+/// A signed integer of any size, used by `Arithmetic::BigInt` for values
+/// which do not fit in an i64
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigInt {
+    neg: bool,
+    // base 2^32 digits, least significant first, without leading zeros
+    mag: Vec<u32>,
+}
+
+// compares two magnitudes
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut mag = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        mag.push(sum as u32);
+        carry = sum >> 32;
+    }
+    mag.push(carry as u32);
+    mag
+}
+
+// subtracts a smaller magnitude from a larger one
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut mag = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &digit) in a.iter().enumerate() {
+        let diff = digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (diff < 0) as i64;
+        mag.push((diff + (borrow << 32)) as u32);
+    }
+    mag
+}
+
+impl BigInt {
+    fn new(neg: bool, mut mag: Vec<u32>) -> Self {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        Self { neg: neg && !mag.is_empty(), mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    /// the value, if it fits in an i64
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let mag = self.low_bits() as i128;
+        let val = if self.neg { -mag } else { mag };
+        i64::try_from(val).ok()
+    }
+
+    /// the value wrapped around to fit in an i64, as with wrapping arithmetic
+    pub fn wrapped(&self) -> i64 {
+        let low = self.low_bits();
+        if self.neg { low.wrapping_neg() as i64 } else { low as i64 }
+    }
+
+    // the lowest 64 bits of the magnitude
+    fn low_bits(&self) -> u64 {
+        self.mag.iter().take(2).rev().fold(0, |acc, &digit| (acc << 32) | digit as u64)
+    }
+
+    // divides the magnitude by a small divisor in place, returning the
+    // remainder
+    fn div_rem_small(mag: &mut [u32], divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for digit in mag.iter_mut().rev() {
+            let cur = (rem << 32) | *digit as u64;
+            *digit = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        rem as u32
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(val: i64) -> Self {
+        let mag = val.unsigned_abs();
+        Self::new(val < 0, vec![mag as u32, (mag >> 32) as u32])
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.neg == other.neg {
+            return BigInt::new(self.neg, add_mag(&self.mag, &other.mag));
+        }
+        // the result takes the sign of the operand with the larger magnitude
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::new(other.neg, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::new(self.neg, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut mag = vec![0u32; self.mag.len() + other.mag.len()];
+        for (i, &a) in self.mag.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.mag.iter().enumerate() {
+                let cur = mag[i + j] as u64 + a as u64 * b as u64 + carry;
+                mag[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            mag[i + other.mag.len()] = carry as u32;
+        }
+        BigInt::new(self.neg != other.neg, mag)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // split off groups of 9 decimal digits, least significant first
+        let mut mag = self.mag.clone();
+        let mut groups = vec![];
+        while mag.iter().any(|&digit| digit != 0) {
+            groups.push(Self::div_rem_small(&mut mag, 1_000_000_000));
+        }
+
+        let mut s = if self.neg { "-".to_owned() } else { String::new() };
+        s += &groups.pop().unwrap().to_string();
+        for group in groups.iter().rev() {
+            s += &format!("{:09}", group);
+        }
+        f.pad(&s)
+    }
+}
+
+/// A string which is not a decimal integer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid integer")
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (neg, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+
+        let ten = BigInt::from(10);
+        let val = digits.bytes().fold(BigInt::default(), |acc, b| {
+            &(&acc * &ten) + &BigInt::from((b - b'0') as i64)
+        });
+        Ok(BigInt::new(neg, val.mag))
+    }
+}
+
+impl Intcode {
+    /// The exact value of an address. Under big-integer arithmetic, a value
+    /// too large for an i64 is kept here, while the address in `memory`
+    /// holds it wrapped around as with wrapping arithmetic.
+    pub fn exact(&self, addr: usize) -> BigInt {
+        match self.wide.get(&addr) {
+            Some(val) => val.clone(),
+            None => BigInt::from(self.memory[addr]),
+        }
+    }
+
+    /// writes a value of any size to memory, as with `Intcode::set`
+    pub fn set_exact(&mut self, pos: usize, val: BigInt) {
+        match val.to_i64() {
+            Some(val) => self.set(pos, val),
+            None => {
+                self.set(pos, val.wrapped());
+                if !self.is_protected(pos, Protection::ReadOnly) {
+                    self.wide.insert(pos, val);
+                }
+            },
+        }
+    }
+
+    // the address which holds a parameter of the current instruction
+    fn param_addr(&self, pn: usize) -> usize {
+        let mode = Instr::decode(self.memory[self.instr_ptr])
+            .map_or(ParameterMode::Position, |instr| instr.param_modes[pn]);
+        let param = self.instr_ptr + pn + 1;
+        match mode {
+            ParameterMode::Position => self.memory[param] as usize,
+            ParameterMode::Immediate => param,
+        }
+    }
+
+    /// the value of a read parameter of the current instruction, if it is too
+    /// large for an i64; for use by opcodes
+    pub fn wide_operand(&self, pn: usize) -> Option<&BigInt> {
+        if self.wide.is_empty() {
+            return None;
+        }
+        self.wide.get(&self.param_addr(pn))
+    }
+
+    /// the exact value of a read parameter of the current instruction, for
+    /// use by opcodes
+    pub fn exact_operand(&self, pn: usize) -> BigInt {
+        self.exact(self.param_addr(pn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::{Arithmetic, Fault, Status};
+
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn converts_to_and_from_i64() {
+        for &val in &[0, 1, -1, 42, i64::MAX, i64::MIN, 1 << 32, -(1 << 32)] {
+            let n = BigInt::from(val);
+            assert_eq!(n.to_i64(), Some(val));
+            assert_eq!(n.wrapped(), val);
+            assert_eq!(n.to_string(), val.to_string());
+            assert_eq!(big(&val.to_string()), n);
+        }
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("9223372036854775808").wrapped(), i64::MIN);
+        assert_eq!(big("-0"), BigInt::default());
+        assert!("12a".parse::<BigInt>().is_err());
+        assert!("-".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let max = BigInt::from(i64::MAX);
+        assert_eq!((&max + &BigInt::from(1)).to_string(), "9223372036854775808");
+        assert_eq!((&max * &max).to_string(), "85070591730234615847396907784232501249");
+        assert_eq!((&BigInt::from(i64::MIN) * &BigInt::from(-1)).to_string(), "9223372036854775808");
+
+        let n = big("-100000000000000000000");
+        assert_eq!((&n + &big("100000000000000000001")).to_i64(), Some(1));
+        assert_eq!((&n + &BigInt::from(7)).to_string(), "-99999999999999999993");
+        assert_eq!((&n * &BigInt::from(0)), BigInt::default());
+        assert!(n < BigInt::from(i64::MIN));
+        assert!(big("100000000000000000000") > BigInt::from(i64::MAX));
+    }
+
+    // cubes the input, outputs whether the cube is less than the input, then
+    // squares the cube and halts
+    const CUBE: &[i64] = &[
+        3, 21, 2, 21, 21, 22, 2, 22, 21, 22, 7, 22, 21, 23, 4, 23, 2, 22, 22, 22, 99,
+        0, 0, 0,
+    ];
+
+    fn run(arithmetic: Arithmetic, input: i64) -> Intcode {
+        let mut prog = Intcode::new(CUBE.to_vec()).with_arithmetic(arithmetic).with_input(input);
+        prog.run();
+        prog
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let input = 2_500_000;
+        let cubed = big("15625000000000000000");
+
+        let prog = run(Arithmetic::Checked, input);
+        assert!(matches!(prog.status, Status::Faulted(Fault::Overflow { instr_ptr: 6, .. })));
+
+        let mut prog = run(Arithmetic::Wrapping, input);
+        assert_eq!(prog.status, Status::Halted);
+        assert_eq!(prog.output(), Some(1));
+
+        let mut prog = run(Arithmetic::Saturating, input);
+        assert_eq!(prog.status, Status::Halted);
+        assert_eq!(prog.output(), Some(0));
+        assert_eq!(prog.memory[22], i64::MAX);
+
+        let mut prog = run(Arithmetic::BigInt, input);
+        assert_eq!(prog.status, Status::Halted);
+        assert_eq!(prog.output(), Some(0));
+        assert_eq!(prog.exact(22), &cubed * &cubed);
+        assert_eq!(prog.memory[22], (&cubed * &cubed).wrapped());
+    }
+
+    #[test]
+    fn wide_values_must_fit_when_used() {
+        // outputs the cube of the input
+        let prog = &[3, 13, 2, 13, 13, 14, 2, 14, 13, 14, 4, 14, 99, 0, 0];
+        let mut small = Intcode::new(prog.to_vec()).with_arithmetic(Arithmetic::BigInt).with_input(-5);
+        small.run();
+        assert_eq!(small.output(), Some(-125));
+
+        let mut large = Intcode::new(prog.to_vec()).with_arithmetic(Arithmetic::BigInt).with_input(3_000_000);
+        large.run();
+        assert_eq!(large.status, Status::Faulted(Fault::TooLarge { instr_ptr: 10, instr: 4 }));
+    }
+
+    #[test]
+    fn rewinds_wide_values() {
+        let mut prog = Intcode::new(CUBE.to_vec())
+            .with_arithmetic(Arithmetic::BigInt)
+            .with_history(16)
+            .with_input(2_500_000);
+        prog.run();
+        assert_eq!(prog.exact(22).to_string(), "244140625000000000000000000000000000000");
+
+        // undo the halt and then the squaring of the cube
+        assert!(prog.step_back() && prog.step_back());
+        assert_eq!(prog.exact(22).to_string(), "15625000000000000000");
+        assert!(prog.rewind_to(0));
+        assert_eq!(prog.memory, CUBE);
+        assert_eq!(prog.exact(22), BigInt::default());
+    }
+}
@@ -0,0 +1,823 @@
+/*
+** src/types/intcode/compile.rs
+** a compiler for a small imperative language targeting Intcode
+*/
+
+//! The language has integer variables, arithmetic, comparisons, `if`/`else`,
+//! `while` loops and input/output statements:
+//!
+//! ```text
+//! # sums inputs until a 0 is read
+//! total = 0;
+//! input x;
+//! while x != 0 {
+//!     total = total + x;
+//!     input x;
+//! }
+//! output total;
+//! ```
+//!
+//! Variables come into existence when they are first assigned or read into,
+//! and start out as 0. Operators, from lowest to highest precedence, are
+//! `||`, `&&`, the comparisons `== != < <= > >=`, `+ -`, `*`, and the unary
+//! `-` and `!`; conditions are true when non-zero. Both sides of `&&` and
+//! `||` are always evaluated. The program halts at the end of the source or
+//! at a `halt;` statement.
+//!
+//! There are no functions: without relative addressing, a function's locals
+//! would need fixed addresses, ruling out recursion.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// An error in the source program, with the line on which it was found
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    /// a character which does not start any token
+    UnexpectedChar { line: usize, ch: char },
+    /// an integer literal which does not fit in an i64
+    InvalidNumber { line: usize, token: String },
+    /// a token which does not fit the grammar
+    Unexpected { line: usize, found: String, expected: &'static str },
+    /// a variable read before it is ever assigned
+    UndefinedVariable { line: usize, name: String },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::UnexpectedChar { line, ch } => {
+                write!(f, "line {}: unexpected character {:?}", line, ch)
+            },
+            CompileError::InvalidNumber { line, token } => {
+                write!(f, "line {}: invalid number {}", line, token)
+            },
+            CompileError::Unexpected { line, found, expected } => {
+                write!(f, "line {}: expected {}, found {}", line, expected, found)
+            },
+            CompileError::UndefinedVariable { line, name } => {
+                write!(f, "line {}: undefined variable {}", line, name)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Sym(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Sym(sym) => write!(f, "{:?}", sym),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+// symbols, with two-character symbols first so that they match greedily
+const SYMBOLS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||",
+    "=", "<", ">", "+", "-", "*", "!", "(", ")", "{", "}", ";",
+];
+
+// splits source into tokens paired with their line numbers; comments run from
+// a '#' to the end of the line
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = vec![];
+
+    for (n, text) in source.lines().enumerate() {
+        let line = n + 1;
+        let text = text.split('#').next().unwrap();
+        let mut rest = text.trim_start();
+
+        while let Some(ch) = rest.chars().next() {
+            let len = if ch.is_ascii_digit() {
+                let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let token = &rest[..len];
+                let num = token.parse::<i64>().map_err(|_| CompileError::InvalidNumber {
+                    line,
+                    token: token.to_string(),
+                })?;
+                tokens.push((Token::Num(num), line));
+                len
+            } else if ch.is_ascii_alphabetic() || ch == '_' {
+                let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(rest[..len].to_string()), line));
+                len
+            } else {
+                let sym = SYMBOLS.iter()
+                    .find(|sym| rest.starts_with(*sym))
+                    .ok_or(CompileError::UnexpectedChar { line, ch })?;
+                tokens.push((Token::Sym(sym), line));
+                sym.len()
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+
+    let last = source.lines().count().max(1);
+    tokens.push((Token::Eof, last));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add, Sub, Mul,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    And, Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Num(i64),
+    Var(String, usize),  // name, and the line it appears on
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Stmt {
+    Assign(String, Expr),
+    Input(String),
+    Output(Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Halt,
+}
+
+const KEYWORDS: [&str; 6] = ["if", "else", "while", "input", "output", "halt"];
+
+// a recursive descent parser over the token stream
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn unexpected(&self, expected: &'static str) -> CompileError {
+        CompileError::Unexpected {
+            line: self.line(),
+            found: self.peek().to_string(),
+            expected,
+        }
+    }
+
+    // consumes the given symbol if it is next
+    fn eat(&mut self, sym: &'static str) -> bool {
+        if *self.peek() == Token::Sym(sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, sym: &'static str) -> Result<(), CompileError> {
+        if self.eat(sym) { Ok(()) } else { Err(self.unexpected(sym)) }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(name) if name == keyword)
+    }
+
+    // a name which is not a keyword
+    fn variable(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            },
+            _ => Err(self.unexpected("a variable")),
+        }
+    }
+
+    fn program(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        let mut stmts = vec![];
+        while *self.peek() != Token::Eof {
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect("{")?;
+        let mut stmts = vec![];
+        while !self.eat("}") {
+            if *self.peek() == Token::Eof {
+                return Err(self.unexpected("}"));
+            }
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        let stmt = if self.is_keyword("if") {
+            self.pos += 1;
+            let cond = self.expr()?;
+            let then = self.block()?;
+            let otherwise = if self.is_keyword("else") {
+                self.pos += 1;
+                // "else if" chains nest as a single statement
+                if self.is_keyword("if") { vec![self.statement()?] } else { self.block()? }
+            } else {
+                vec![]
+            };
+            return Ok(Stmt::If(cond, then, otherwise));
+        } else if self.is_keyword("while") {
+            self.pos += 1;
+            let cond = self.expr()?;
+            return Ok(Stmt::While(cond, self.block()?));
+        } else if self.is_keyword("input") {
+            self.pos += 1;
+            Stmt::Input(self.variable()?)
+        } else if self.is_keyword("output") {
+            self.pos += 1;
+            Stmt::Output(self.expr()?)
+        } else if self.is_keyword("halt") {
+            self.pos += 1;
+            Stmt::Halt
+        } else {
+            let name = self.variable().map_err(|_| self.unexpected("a statement"))?;
+            self.expect("=")?;
+            Stmt::Assign(name, self.expr()?)
+        };
+
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    // parses binary operators of at least the given precedence level
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        const LEVELS: [&[(&str, BinOp)]; 5] = [
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[("==", BinOp::Eq), ("!=", BinOp::Ne), ("<=", BinOp::Le),
+              (">=", BinOp::Ge), ("<", BinOp::Lt), (">", BinOp::Gt)],
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            &[("*", BinOp::Mul)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = LEVELS[level].iter().find(|(sym, _)| *self.peek() == Token::Sym(sym)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            Ok(match self.unary()? {
+                // fold negative literals so that they can be immediates
+                Expr::Num(n) => Expr::Num(-n),
+                expr => Expr::Neg(Box::new(expr)),
+            })
+        } else if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        let line = self.line();
+        match self.peek().clone() {
+            Token::Num(n) => {
+                self.pos += 1;
+                Ok(Expr::Num(n))
+            },
+            Token::Ident(_) => {
+                let name = self.variable().map_err(|_| self.unexpected("an expression"))?;
+                Ok(Expr::Var(name, line))
+            },
+            Token::Sym("(") => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            },
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+}
+
+// a memory cell whose address is only known once the code has been laid out
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Word {
+    Lit(i64),      // a literal value
+    Var(usize),    // the address of a variable
+    Temp(usize),   // the address of a temporary
+    Label(usize),  // the address of a label in the code
+}
+
+// an instruction parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Arg {
+    Imm(i64),    // an immediate-mode value
+    Mem(Word),   // a position-mode address
+}
+
+impl Arg {
+    fn mode(&self) -> i64 {
+        match self {
+            Arg::Imm(_) => 1,
+            Arg::Mem(_) => 0,
+        }
+    }
+
+    fn word(&self) -> Word {
+        match *self {
+            Arg::Imm(val) => Word::Lit(val),
+            Arg::Mem(word) => word,
+        }
+    }
+}
+
+const ADD: i64 = 1;
+const MUL: i64 = 2;
+const IN: i64 = 3;
+const OUT: i64 = 4;
+const JT: i64 = 5;
+const JF: i64 = 6;
+const LT: i64 = 7;
+const EQ: i64 = 8;
+const HALT: i64 = 99;
+
+// generates code with symbolic addresses, which are resolved once the number
+// of variables and temporaries is known
+struct Codegen {
+    code: Vec<Word>,
+    vars: HashMap<String, usize>,
+    labels: Vec<Option<usize>>,
+    // temporaries in use by the current statement, and the most ever in use
+    temps: usize,
+    max_temps: usize,
+}
+
+impl Codegen {
+    fn emit(&mut self, opcode: i64, args: &[Arg]) {
+        let modes = args.iter()
+            .enumerate()
+            .map(|(n, arg)| arg.mode() * 10i64.pow(n as u32 + 2))
+            .sum::<i64>();
+        self.code.push(Word::Lit(opcode + modes));
+        self.code.extend(args.iter().map(Arg::word));
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place_label(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn temp(&mut self) -> Arg {
+        self.temps += 1;
+        self.max_temps = self.max_temps.max(self.temps);
+        Arg::Mem(Word::Temp(self.temps - 1))
+    }
+
+    // the variable with the given name, creating it if needed
+    fn define(&mut self, name: &str) -> Arg {
+        let next = self.vars.len();
+        Arg::Mem(Word::Var(*self.vars.entry(name.to_string()).or_insert(next)))
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        for stmt in stmts {
+            self.statement(stmt)?;
+            // temporaries only live as long as a statement
+            self.temps = 0;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Assign(name, expr) => {
+                // operators store their result straight into a variable which
+                // already exists; a new variable is only defined once the
+                // right-hand side is compiled, so that it cannot refer to it
+                let dst = self.vars.contains_key(name).then(|| self.define(name));
+                let arg = self.expr(expr, dst)?;
+                let dst = self.define(name);
+                if arg != dst {
+                    self.assign(arg, dst);
+                }
+            },
+            Stmt::Input(name) => {
+                let dst = self.define(name);
+                self.emit(IN, &[dst]);
+            },
+            Stmt::Output(expr) => {
+                let arg = self.expr(expr, None)?;
+                self.emit(OUT, &[arg]);
+            },
+            Stmt::If(cond, then, otherwise) => {
+                let else_label = self.new_label();
+                let cond = self.expr(cond, None)?;
+                self.temps = 0;
+                self.emit(JF, &[cond, Arg::Imm(0)]);
+                self.patch_jump(else_label);
+                self.block(then)?;
+
+                if otherwise.is_empty() {
+                    self.place_label(else_label);
+                } else {
+                    let end_label = self.new_label();
+                    self.emit(JT, &[Arg::Imm(1), Arg::Imm(0)]);
+                    self.patch_jump(end_label);
+                    self.place_label(else_label);
+                    self.block(otherwise)?;
+                    self.place_label(end_label);
+                }
+            },
+            Stmt::While(cond, body) => {
+                let top_label = self.new_label();
+                let end_label = self.new_label();
+                self.place_label(top_label);
+                let cond = self.expr(cond, None)?;
+                self.temps = 0;
+                self.emit(JF, &[cond, Arg::Imm(0)]);
+                self.patch_jump(end_label);
+                self.block(body)?;
+                self.emit(JT, &[Arg::Imm(1), Arg::Imm(0)]);
+                self.patch_jump(top_label);
+                self.place_label(end_label);
+            },
+            Stmt::Halt => self.emit(HALT, &[]),
+        }
+        Ok(())
+    }
+
+    // replaces the target of the jump just emitted with a label
+    fn patch_jump(&mut self, label: usize) {
+        let last = self.code.len() - 1;
+        self.code[last] = Word::Label(label);
+    }
+
+    // copies a value into a variable
+    fn assign(&mut self, src: Arg, dst: Arg) {
+        self.emit(ADD, &[src, Arg::Imm(0), dst]);
+    }
+
+    // Compiles an expression, returning where its value can be found. The
+    // result of an operator is stored in `dst` if given, or else in a new
+    // temporary.
+    fn expr(&mut self, expr: &Expr, dst: Option<Arg>) -> Result<Arg, CompileError> {
+        let (opcode, lhs, rhs) = match expr {
+            Expr::Num(n) => return Ok(Arg::Imm(*n)),
+            Expr::Var(name, line) => {
+                return match self.vars.get(name) {
+                    Some(&var) => Ok(Arg::Mem(Word::Var(var))),
+                    None => Err(CompileError::UndefinedVariable { line: *line, name: name.clone() }),
+                }
+            },
+            Expr::Neg(expr) => (MUL, self.expr(expr, None)?, Arg::Imm(-1)),
+            Expr::Not(expr) => (EQ, self.expr(expr, None)?, Arg::Imm(0)),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(lhs, None)?;
+                let rhs = self.expr(rhs, None)?;
+                match op {
+                    BinOp::Add => (ADD, lhs, rhs),
+                    BinOp::Mul => (MUL, lhs, rhs),
+                    BinOp::Lt  => (LT, lhs, rhs),
+                    BinOp::Gt  => (LT, rhs, lhs),
+                    BinOp::Eq  => (EQ, lhs, rhs),
+                    BinOp::Sub => {
+                        let neg = self.temp();
+                        self.emit(MUL, &[rhs, Arg::Imm(-1), neg]);
+                        (ADD, lhs, neg)
+                    },
+                    // the negations of the comparisons above
+                    BinOp::Ne | BinOp::Le | BinOp::Ge => {
+                        let (opcode, lhs, rhs) = match op {
+                            BinOp::Ne => (EQ, lhs, rhs),
+                            BinOp::Le => (LT, rhs, lhs),
+                            _         => (LT, lhs, rhs),
+                        };
+                        let res = self.temp();
+                        self.emit(opcode, &[lhs, rhs, res]);
+                        (EQ, res, Arg::Imm(0))
+                    },
+                    // both are true if non-zero, so normalize them to 0 or 1
+                    // by comparing to 0 first
+                    BinOp::And | BinOp::Or => {
+                        let (lhs_zero, rhs_zero) = (self.temp(), self.temp());
+                        self.emit(EQ, &[lhs, Arg::Imm(0), lhs_zero]);
+                        self.emit(EQ, &[rhs, Arg::Imm(0), rhs_zero]);
+                        let res = self.temp();
+                        if *op == BinOp::And {
+                            // neither is zero
+                            self.emit(ADD, &[lhs_zero, rhs_zero, res]);
+                        } else {
+                            // not both are zero
+                            self.emit(MUL, &[lhs_zero, rhs_zero, res]);
+                        }
+                        (EQ, res, Arg::Imm(0))
+                    },
+                }
+            },
+        };
+
+        let dst = match dst {
+            Some(dst) => dst,
+            None => self.temp(),
+        };
+        self.emit(opcode, &[lhs, rhs, dst]);
+        Ok(dst)
+    }
+
+    // lays out the code, followed by the variables and then the temporaries,
+    // and resolves every symbolic address
+    fn finish(mut self) -> Vec<i64> {
+        self.emit(HALT, &[]);
+
+        let vars = self.code.len();
+        let temps = vars + self.vars.len();
+        let mut memory = self.code.iter()
+            .map(|word| match *word {
+                Word::Lit(val) => val,
+                Word::Var(n) => (vars + n) as i64,
+                Word::Temp(n) => (temps + n) as i64,
+                Word::Label(n) => self.labels[n].unwrap() as i64,
+            })
+            .collect::<Vec<_>>();
+        memory.resize(temps + self.max_temps, 0);
+        memory
+    }
+}
+
+/// Compiles a program written in the language described in this module into
+/// an Intcode memory image which can be run with `Intcode::new`.
+pub fn compile(source: &str) -> Result<Vec<i64>, CompileError> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let program = parser.program()?;
+
+    let mut codegen = Codegen {
+        code: vec![],
+        vars: HashMap::new(),
+        labels: vec![],
+        temps: 0,
+        max_temps: 0,
+    };
+    codegen.block(&program)?;
+    Ok(codegen.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::{Fault, Intcode, Status};
+
+    // compiles and runs a program with the given input, returning its output
+    fn run(source: &str, input: &[i64]) -> Vec<i64> {
+        let memory = compile(source).unwrap_or_else(|err| panic!("{}", err));
+        let mut prog = Intcode::new(memory);
+        for &val in input {
+            prog.input(val);
+        }
+        prog.run();
+        assert_eq!(prog.status, Status::Halted);
+
+        let mut output = vec![];
+        while let Some(val) = prog.output() {
+            output.push(val);
+        }
+        output
+    }
+
+    #[test]
+    fn empty_program_halts() {
+        assert_eq!(compile("").unwrap(), vec![99]);
+        assert_eq!(run("# nothing to see here\n", &[]), vec![]);
+    }
+
+    #[test]
+    fn echoes_input() {
+        assert_eq!(run("input x; output x;", &[42]), vec![42]);
+        assert_eq!(run("input x; input y; output y; output x;", &[1, 2]), vec![2, 1]);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let source = "
+            input a;
+            input b;
+            output a + b;
+            output a - b;
+            output a * b;
+            output -a;
+            output 2 + 3 * 4;
+            output (2 + 3) * 4;
+            output a - b - 1;
+            output -(a * -b);
+        ";
+        assert_eq!(run(source, &[7, 3]), vec![10, 4, 21, -7, 14, 20, 3, 21]);
+        assert_eq!(run(source, &[-2, 5]), vec![3, -7, -10, 2, 14, 20, -8, -10]);
+    }
+
+    #[test]
+    fn comparisons() {
+        let source = "
+            input a;
+            input b;
+            output a == b;
+            output a != b;
+            output a < b;
+            output a <= b;
+            output a > b;
+            output a >= b;
+        ";
+        assert_eq!(run(source, &[1, 2]), vec![0, 1, 1, 1, 0, 0]);
+        assert_eq!(run(source, &[2, 2]), vec![1, 0, 0, 1, 0, 1]);
+        assert_eq!(run(source, &[3, 2]), vec![0, 1, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn logical_operators() {
+        let source = "
+            input a;
+            input b;
+            output a && b;
+            output a || b;
+            output !a;
+            output a < 10 && b > 5 || a == b;
+        ";
+        assert_eq!(run(source, &[0, 0]), vec![0, 0, 1, 1]);
+        assert_eq!(run(source, &[5, 0]), vec![0, 1, 0, 0]);
+        assert_eq!(run(source, &[-3, 7]), vec![1, 1, 0, 1]);
+        assert_eq!(run(source, &[20, 20]), vec![1, 1, 0, 1]);
+    }
+
+    #[test]
+    fn if_else_chains() {
+        let source = "
+            input x;
+            if x < 0 {
+                output -1;
+            } else if x == 0 {
+                output 0;
+            } else {
+                output 1;
+            }
+            if x { output 100; }
+        ";
+        assert_eq!(run(source, &[-5]), vec![-1, 100]);
+        assert_eq!(run(source, &[0]), vec![0]);
+        assert_eq!(run(source, &[9]), vec![1, 100]);
+    }
+
+    #[test]
+    fn while_loops() {
+        let factorial = "
+            input n;
+            acc = 1;
+            while n > 1 {
+                acc = acc * n;
+                n = n - 1;
+            }
+            output acc;
+        ";
+        assert_eq!(run(factorial, &[0]), vec![1]);
+        assert_eq!(run(factorial, &[5]), vec![120]);
+        assert_eq!(run(factorial, &[20]), vec![2432902008176640000]);
+
+        let fibonacci = "
+            input n;
+            a = 0;
+            b = 1;
+            while n > 0 {
+                output a;
+                next = a + b;
+                a = b;
+                b = next;
+                n = n - 1;
+            }
+        ";
+        assert_eq!(run(fibonacci, &[10]), vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+    }
+
+    #[test]
+    fn nested_loops_and_halt() {
+        let source = "
+            # prints the primes below the input, stopping after the fifth
+            input limit;
+            count = 0;
+            n = 2;
+            while n < limit {
+                d = 2;
+                prime = 1;
+                while d * d <= n && prime {
+                    # trial division by repeated subtraction
+                    r = n;
+                    while r >= d { r = r - d; }
+                    if r == 0 { prime = 0; }
+                    d = d + 1;
+                }
+                if prime {
+                    output n;
+                    count = count + 1;
+                    if count == 5 { halt; }
+                }
+                n = n + 1;
+            }
+        ";
+        assert_eq!(run(source, &[10]), vec![2, 3, 5, 7]);
+        assert_eq!(run(source, &[100]), vec![2, 3, 5, 7, 11]);
+    }
+
+    #[test]
+    fn sums_inputs_until_zero() {
+        let source = "
+            total = 0;
+            input x;
+            while x != 0 {
+                total = total + x;
+                input x;
+            }
+            output total;
+        ";
+        assert_eq!(run(source, &[1, 2, 3, 4, 0]), vec![10]);
+
+        // without the terminating zero the program waits for more input
+        let mut prog = Intcode::new(compile(source).unwrap()).with_input(5);
+        prog.run();
+        assert_eq!(prog.status, Status::Waiting);
+        prog.input(0);
+        prog.run();
+        assert_eq!(prog.output(), Some(5));
+    }
+
+    #[test]
+    fn overflow_faults() {
+        let source = "input x; output x * x;";
+        let mut prog = Intcode::new(compile(source).unwrap()).with_input(i64::MAX);
+        prog.run();
+        assert!(matches!(prog.status, Status::Faulted(Fault::Overflow { .. })));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(
+            compile("x = 1;\noutput y;"),
+            Err(CompileError::UndefinedVariable { line: 2, name: "y".to_string() }));
+        assert_eq!(
+            compile("x = x + 1;"),
+            Err(CompileError::UndefinedVariable { line: 1, name: "x".to_string() }));
+        assert_eq!(
+            compile("x = 1\noutput x;"),
+            Err(CompileError::Unexpected {
+                line: 2,
+                found: "output".to_string(),
+                expected: ";",
+            }));
+        assert_eq!(
+            compile("while 1 {\n  output 1;\n"),
+            Err(CompileError::Unexpected {
+                line: 2,
+                found: "end of input".to_string(),
+                expected: "}",
+            }));
+        assert_eq!(
+            compile("output 1 / 2;"),
+            Err(CompileError::UnexpectedChar { line: 1, ch: '/' }));
+        assert_eq!(
+            compile("output 99999999999999999999;"),
+            Err(CompileError::InvalidNumber {
+                line: 1,
+                token: "99999999999999999999".to_string(),
+            }));
+        assert!(matches!(compile("if = 3;"), Err(CompileError::Unexpected { .. })));
+    }
+}
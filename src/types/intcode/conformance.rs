@@ -0,0 +1,292 @@
+/*
+** src/types/intcode/conformance.rs
+** reference cases from the puzzle statements which every VM change must pass
+*/
+
+use std::iter;
+
+use crate::types::intcode::{Intcode, IntcodeChain, PhaseSearch, Schedule, Status};
+use crate::utils::Permutations;
+
+// A program along with its input and what it must produce. Programs must
+// halt; `memory` is the expected final memory, if the puzzle gives one.
+struct Case {
+    program: &'static [i64],
+    input: &'static [i64],
+    output: &'static [i64],
+    memory: Option<&'static [i64]>,
+}
+
+// Reference cases, grouped by the opcode or parameter mode they exercise.
+// Cases for new opcodes and modes belong in a new group here, with a test
+// below which checks it.
+
+// day 2: add (1), multiply (2) and halt (99), in position mode
+const ARITHMETIC: &[Case] = &[
+    Case {
+        program: &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+        input: &[],
+        output: &[],
+        memory: Some(&[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]),
+    },
+    Case {
+        program: &[1, 0, 0, 0, 99],
+        input: &[],
+        output: &[],
+        memory: Some(&[2, 0, 0, 0, 99]),
+    },
+    Case {
+        program: &[2, 3, 0, 3, 99],
+        input: &[],
+        output: &[],
+        memory: Some(&[2, 3, 0, 6, 99]),
+    },
+    Case {
+        program: &[2, 4, 4, 5, 99, 0],
+        input: &[],
+        output: &[],
+        memory: Some(&[2, 4, 4, 5, 99, 9801]),
+    },
+    Case {
+        program: &[1, 1, 1, 4, 99, 5, 6, 0, 99],
+        input: &[],
+        output: &[],
+        memory: Some(&[30, 1, 1, 4, 2, 5, 6, 0, 99]),
+    },
+];
+
+// day 5: input (3) and output (4)
+const INPUT_OUTPUT: &[Case] = &[
+    Case { program: &[3, 0, 4, 0, 99], input: &[42], output: &[42], memory: Some(&[42, 0, 4, 0, 99]) },
+    Case { program: &[3, 0, 4, 0, 99], input: &[-7], output: &[-7], memory: None },
+];
+
+// day 5: immediate mode, including negative values
+const IMMEDIATE_MODE: &[Case] = &[
+    Case {
+        program: &[1002, 4, 3, 4, 33],
+        input: &[],
+        output: &[],
+        memory: Some(&[1002, 4, 3, 4, 99]),
+    },
+    Case {
+        program: &[1101, 100, -1, 4, 0],
+        input: &[],
+        output: &[],
+        memory: Some(&[1101, 100, -1, 4, 99]),
+    },
+];
+
+// day 5: less than (7) and equals (8), comparing the input to 8
+const EQUAL_8_POSITION: &[i64] = &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+const LESS_8_POSITION: &[i64] = &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+const EQUAL_8_IMMEDIATE: &[i64] = &[3, 3, 1108, -1, 8, 3, 4, 3, 99];
+const LESS_8_IMMEDIATE: &[i64] = &[3, 3, 1107, -1, 8, 3, 4, 3, 99];
+
+const COMPARISONS: &[Case] = &[
+    Case { program: EQUAL_8_POSITION, input: &[8], output: &[1], memory: None },
+    Case { program: EQUAL_8_POSITION, input: &[7], output: &[0], memory: None },
+    Case { program: EQUAL_8_POSITION, input: &[9], output: &[0], memory: None },
+    Case { program: LESS_8_POSITION, input: &[7], output: &[1], memory: None },
+    Case { program: LESS_8_POSITION, input: &[8], output: &[0], memory: None },
+    Case { program: LESS_8_POSITION, input: &[-100], output: &[1], memory: None },
+    Case { program: EQUAL_8_IMMEDIATE, input: &[8], output: &[1], memory: None },
+    Case { program: EQUAL_8_IMMEDIATE, input: &[0], output: &[0], memory: None },
+    Case { program: LESS_8_IMMEDIATE, input: &[7], output: &[1], memory: None },
+    Case { program: LESS_8_IMMEDIATE, input: &[8], output: &[0], memory: None },
+];
+
+// day 5: jump-if-true (5) and jump-if-false (6), outputting 0 if the input
+// was 0 and 1 otherwise
+const JUMP_POSITION: &[i64] = &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+const JUMP_IMMEDIATE: &[i64] = &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+
+// day 5: outputs 999 if the input is below 8, 1000 if it is 8 and 1001 if
+// it is above 8
+const COMPARE_TO_8: &[i64] = &[
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31,
+    1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104,
+    999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99,
+];
+
+const JUMPS: &[Case] = &[
+    Case { program: JUMP_POSITION, input: &[0], output: &[0], memory: None },
+    Case { program: JUMP_POSITION, input: &[5], output: &[1], memory: None },
+    Case { program: JUMP_IMMEDIATE, input: &[0], output: &[0], memory: None },
+    Case { program: JUMP_IMMEDIATE, input: &[-3], output: &[1], memory: None },
+    Case { program: COMPARE_TO_8, input: &[-5], output: &[999], memory: None },
+    Case { program: COMPARE_TO_8, input: &[7], output: &[999], memory: None },
+    Case { program: COMPARE_TO_8, input: &[8], output: &[1000], memory: None },
+    Case { program: COMPARE_TO_8, input: &[9], output: &[1001], memory: None },
+    Case { program: COMPARE_TO_8, input: &[1000], output: &[1001], memory: None },
+];
+
+// An amplifier controller software program, with the phase setting sequence
+// which produces the highest thruster signal and that signal
+struct Amplifier {
+    program: &'static [i64],
+    phases: [i64; 5],
+    signal: i64,
+}
+
+// day 7: amplifiers connected in series
+const AMPLIFIER_CHAINS: &[Amplifier] = &[
+    Amplifier {
+        program: &[3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0],
+        phases: [4, 3, 2, 1, 0],
+        signal: 43210,
+    },
+    Amplifier {
+        program: &[
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23,
+            1, 24, 23, 23, 4, 23, 99, 0, 0,
+        ],
+        phases: [0, 1, 2, 3, 4],
+        signal: 54321,
+    },
+    Amplifier {
+        program: &[
+            3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33,
+            1002, 33, 7, 33, 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
+        ],
+        phases: [1, 0, 4, 3, 2],
+        signal: 65210,
+    },
+];
+
+// day 7: amplifiers connected in a feedback loop
+const AMPLIFIER_LOOPS: &[Amplifier] = &[
+    Amplifier {
+        program: &[
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4,
+            27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5,
+        ],
+        phases: [9, 8, 7, 6, 5],
+        signal: 139629729,
+    },
+    Amplifier {
+        program: &[
+            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55,
+            1005, 55, 26, 1001, 54, -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008,
+            54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4, 53, 1001, 56, -1,
+            56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+        ],
+        phases: [9, 7, 8, 5, 6],
+        signal: 18216,
+    },
+];
+
+fn check(cases: &[Case]) {
+    for (n, case) in cases.iter().enumerate() {
+        let mut prog = Intcode::new(case.program.to_vec());
+        for &val in case.input {
+            prog.input(val);
+        }
+        prog.run();
+
+        assert_eq!(prog.status, Status::Halted, "case {}", n);
+        let output = iter::from_fn(|| prog.output()).collect::<Vec<_>>();
+        assert_eq!(output, case.output, "case {}: output", n);
+        if let Some(memory) = case.memory {
+            assert_eq!(prog.memory, memory, "case {}: memory", n);
+        }
+    }
+}
+
+// the thruster signal produced by a chain of amplifiers
+fn thruster_signal(program: &[i64], phases: &[i64], feedback: bool, schedule: Schedule) -> i64 {
+    let mut chain = IntcodeChain::from(phases.iter()
+        .map(|&phase| Intcode::new(program.to_vec()).with_input(phase)))
+        .with_schedule(schedule);
+    if feedback {
+        chain = chain.with_feedback();
+    }
+
+    chain.input(0);
+    chain.run();
+    chain.output().expect("amplifiers produced no signal")
+}
+
+fn check_amplifiers(amps: &[Amplifier], phase_settings: [i64; 5], feedback: bool) {
+    for (n, amp) in amps.iter().enumerate() {
+        let signal = thruster_signal(amp.program, &amp.phases, feedback, Schedule::RoundRobin);
+        assert_eq!(signal, amp.signal, "case {}", n);
+
+        // no other phase setting sequence produces a higher signal
+        let best = Permutations::new(&phase_settings)
+            .map(|phases| phases.into_iter().cloned().collect::<Vec<_>>())
+            .map(|phases| thruster_signal(amp.program, &phases, feedback, Schedule::RoundRobin))
+            .max();
+        assert_eq!(best, Some(amp.signal), "case {}: best signal", n);
+
+        let mut search = PhaseSearch::new(amp.program).with_threads(3);
+        if feedback {
+            search = search.with_feedback();
+        }
+        let best = search.run(&phase_settings).expect("amplifiers produced no signal");
+        assert_eq!((best.phases.as_slice(), best.signal), (&amp.phases[..], amp.signal), "case {}: search", n);
+    }
+}
+
+// every schedule must produce the same signal as running each program in turn
+fn check_schedules(amps: &[Amplifier], feedback: bool) {
+    let schedules = [
+        Schedule::RoundRobin,
+        Schedule::Reverse,
+        Schedule::TimeSlice(1),
+        Schedule::TimeSlice(3),
+        Schedule::TimeSlice(1000),
+    ];
+    for (n, amp) in amps.iter().enumerate() {
+        for &schedule in schedules.iter() {
+            let signal = thruster_signal(amp.program, &amp.phases, feedback, schedule);
+            assert_eq!(signal, amp.signal, "case {}: {:?}", n, schedule);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        check(ARITHMETIC);
+    }
+
+    #[test]
+    fn input_output() {
+        check(INPUT_OUTPUT);
+    }
+
+    #[test]
+    fn immediate_mode() {
+        check(IMMEDIATE_MODE);
+    }
+
+    #[test]
+    fn comparisons() {
+        check(COMPARISONS);
+    }
+
+    #[test]
+    fn jumps() {
+        check(JUMPS);
+    }
+
+    #[test]
+    fn amplifier_chains() {
+        check_amplifiers(AMPLIFIER_CHAINS, [0, 1, 2, 3, 4], false);
+    }
+
+    #[test]
+    fn amplifier_feedback_loops() {
+        check_amplifiers(AMPLIFIER_LOOPS, [5, 6, 7, 8, 9], true);
+    }
+
+    #[test]
+    fn amplifier_schedules() {
+        check_schedules(AMPLIFIER_CHAINS, false);
+        check_schedules(AMPLIFIER_LOOPS, true);
+    }
+}
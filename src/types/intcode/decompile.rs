@@ -0,0 +1,528 @@
+/*
+** src/types/intcode/decompile.rs
+** lifting of Intcode programs into structured pseudo-code
+*/
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+
+use crate::types::intcode::analysis::{DecodeError, Disassembly, Instruction, Target};
+use crate::types::intcode::{OpcodeTable, Param, ParameterMode};
+
+// the condition under which a conditional jump is taken
+#[derive(Clone, Debug)]
+enum Cond {
+    Cmp(String, &'static str, String),
+    Value(String, bool),  // a value, and whether or not it is negated
+}
+
+impl Cond {
+    fn negate(self) -> Self {
+        match self {
+            Cond::Cmp(lhs, op, rhs) => {
+                let op = match op {
+                    "<"  => ">=",
+                    ">=" => "<",
+                    "==" => "!=",
+                    _    => "==",
+                };
+                Cond::Cmp(lhs, op, rhs)
+            },
+            Cond::Value(val, negated) => Cond::Value(val, !negated),
+        }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cond::Cmp(lhs, op, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            Cond::Value(val, false) => write!(f, "{}", val),
+            Cond::Value(val, true) => write!(f, "!{}", val),
+        }
+    }
+}
+
+// the loop enclosing the code being emitted, for breaks and continues
+#[derive(Clone, Copy)]
+struct Loop {
+    head: usize,
+    exit: usize,
+}
+
+struct Decompiler<'a> {
+    disasm: &'a Disassembly,
+    memory: &'a [i64],
+    // addresses which are given a label, and those referred to by a goto
+    labels: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+    // data addresses which were named as variables
+    vars: BTreeSet<usize>,
+    out: String,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(disasm: &'a Disassembly, memory: &'a [i64]) -> Self {
+        Self {
+            disasm,
+            memory,
+            labels: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            vars: BTreeSet::new(),
+            out: String::new(),
+        }
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        writeln!(self.out, "{:width$}{}", "", text, width = depth * 4).unwrap();
+    }
+
+    // the name of a fixed address: data is named as a variable, while cells
+    // inside the code, including words which could not be decoded, are shown
+    // as raw memory
+    fn name(&mut self, addr: i64) -> String {
+        let code = |addr| self.disasm.is_code(addr) || self.disasm.errors.contains_key(&addr);
+        if addr < 0 || addr as usize >= self.memory.len() || code(addr as usize) {
+            format!("m[{}]", addr)
+        } else {
+            self.vars.insert(addr as usize);
+            format!("v{}", addr)
+        }
+    }
+
+    // the value of a read operand; patched operands are only known at runtime
+    // so are read from the operand cell itself
+    fn read(&mut self, instr: &Instruction, pn: usize) -> String {
+        let op = instr.operands[pn];
+        let cell = instr.addr + pn + 1;
+        match (op.mode, op.patched) {
+            (ParameterMode::Immediate, false) => op.value.to_string(),
+            (ParameterMode::Position, false) => self.name(op.value),
+            (ParameterMode::Immediate, true) => format!("m[{}]", cell),
+            (ParameterMode::Position, true) => format!("m[m[{}]]", cell),
+        }
+    }
+
+    fn write(&mut self, instr: &Instruction, pn: usize) -> String {
+        let op = instr.operands[pn];
+        if op.patched {
+            format!("m[m[{}]]", instr.addr + pn + 1)
+        } else {
+            self.name(op.value)
+        }
+    }
+
+    fn goto(&mut self, target: Option<Target>, instr: &Instruction) -> String {
+        match target {
+            Some(Target::Static(addr)) => {
+                self.gotos.insert(addr);
+                format!("goto L{};", addr)
+            },
+            _ => format!("goto *{};", self.read(instr, 1)),
+        }
+    }
+
+    // Whether or not the value at an address is dead after an instruction:
+    // on every path onward it is overwritten before it is read. Paths through
+    // dynamic jumps or reads of patched operands are assumed to read it.
+    fn dead_after(&self, addr: i64, instr: &Instruction) -> bool {
+        let mut queue = instr.successors();
+        let mut seen = BTreeSet::new();
+
+        while let Some(target) = queue.pop() {
+            let next = match target {
+                Target::Static(next) => next,
+                Target::Dynamic => return false,
+            };
+            if !seen.insert(next) {
+                continue;
+            }
+            let instr = match self.disasm.instrs.get(&next) {
+                Some(instr) => instr,
+                None => return false,
+            };
+
+            let reads = instr.operands.iter().any(|op| {
+                op.read_addr() == Some(addr)
+                    || (op.patched && op.kind == Param::Read && op.mode == ParameterMode::Position)
+            });
+            if reads {
+                return false;
+            }
+            if instr.operands.iter().all(|op| op.write_addr() != Some(addr)) {
+                queue.extend(instr.successors());
+            }
+        }
+        true
+    }
+
+    // If a conditional jump starts at the given address, returns the
+    // condition under which it is taken along with the jump itself. A
+    // comparison into a temporary which is dead once the following jump has
+    // tested it is folded into the condition.
+    fn branch_at(&mut self, addr: usize) -> Option<(Cond, &'a Instruction)> {
+        let disasm = self.disasm;
+        let instr = disasm.instrs.get(&addr)?;
+
+        let (cond, jump) = if instr.opcode == 7 || instr.opcode == 8 {
+            let jump = disasm.instrs.get(&instr.next_addr())?;
+            let dst = instr.operands[2].write_addr()?;
+            if !jump.is_jump() || disasm.jump_targets.contains(&jump.addr)
+                || self.labels.contains(&jump.addr)
+                || jump.operands[0].read_addr() != Some(dst)
+                || jump.operands[1].read_addr() == Some(dst)
+                || !self.dead_after(dst, jump)
+            {
+                return None;
+            }
+            let op = if instr.opcode == 7 { "<" } else { "==" };
+            (Cond::Cmp(self.read(instr, 0), op, self.read(instr, 1)), jump)
+        } else if instr.is_jump() {
+            (Cond::Value(self.read(instr, 0), false), instr)
+        } else {
+            return None;
+        };
+
+        if !jump.falls_through() || jump.jump_target().is_none() {
+            return None;
+        }
+        let cond = if jump.opcode == 5 { cond } else { cond.negate() };
+        Some((cond, jump))
+    }
+
+    // the first instruction or undecodable address in [lo, hi)
+    fn next_addr(&self, lo: usize, hi: usize) -> Option<usize> {
+        let instr = self.disasm.instrs.range(lo..hi).next().map(|(&addr, _)| addr);
+        let error = self.disasm.errors.range(lo..hi).next().map(|(&addr, _)| addr);
+        match (instr, error) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // the end of the loop headed at the given address, if any jump in [head,
+    // hi) goes back to it
+    fn loop_end(&self, head: usize, hi: usize) -> Option<usize> {
+        self.disasm.instrs.range(head..hi)
+            .map(|(_, instr)| instr)
+            .filter(|instr| instr.jump_target() == Some(Target::Static(head)))
+            .map(|instr| instr.next_addr())
+            .filter(|&end| end <= hi)
+            .max()
+    }
+
+    fn emit_loop(&mut self, head: usize, end: usize, depth: usize) {
+        let ctx = Some(Loop { head, exit: end });
+
+        match self.branch_at(head) {
+            // a loop which starts by testing whether to exit is a while loop
+            Some((cond, jump)) if jump.jump_target() == Some(Target::Static(end)) => {
+                self.line(depth, &format!("while {} {{", cond.negate()));
+                self.emit_range(jump.next_addr(), end, ctx, depth + 1, None);
+            },
+            _ => {
+                self.line(depth, "loop {");
+                self.emit_range(head, end, ctx, depth + 1, Some(head));
+            },
+        }
+        self.line(depth, "}");
+    }
+
+    // emits a conditional jump, returning the address to continue from
+    fn emit_branch(
+        &mut self, cond: Cond, jump: &Instruction, hi: usize, ctx: Option<Loop>, depth: usize,
+    ) -> usize {
+        let next = jump.next_addr();
+        let target = jump.jump_target();
+
+        match (target, ctx) {
+            (Some(Target::Static(t)), Some(ctx)) if t == ctx.exit => {
+                self.line(depth, &format!("if {} {{ break; }}", cond));
+                return next;
+            },
+            (Some(Target::Static(t)), Some(ctx)) if t == ctx.head => {
+                if next == hi {
+                    self.line(depth, &format!("if {} {{ break; }}", cond.negate()));
+                } else {
+                    self.line(depth, &format!("if {} {{ continue; }}", cond));
+                }
+                return next;
+            },
+            _ => {},
+        }
+
+        let t = match target {
+            Some(Target::Static(t)) if t > next && t <= hi => t,
+            _ => {
+                let goto = self.goto(target, jump);
+                self.line(depth, &format!("if {} {{ {} }}", cond, goto));
+                return next;
+            },
+        };
+
+        // the code between the jump and its target runs when it is not
+        // taken; if that code ends by jumping further forward, the code it
+        // jumps over is the else branch
+        let skip = self.disasm.instrs.range(next..t)
+            .next_back()
+            .map(|(_, instr)| instr)
+            .filter(|instr| instr.next_addr() == t && !instr.falls_through());
+        let end = match (skip.and_then(|instr| instr.jump_target()), ctx) {
+            (Some(Target::Static(e)), ctx) if e > t && e <= hi
+                && ctx.is_none_or(|ctx| e != ctx.exit) => Some((skip.unwrap().addr, e)),
+            _ => None,
+        };
+
+        self.line(depth, &format!("if {} {{", cond.negate()));
+        match end {
+            Some((skip, e)) => {
+                self.emit_range(next, skip, ctx, depth + 1, None);
+                self.line(depth, "} else {");
+                self.emit_range(t, e, ctx, depth + 1, None);
+                self.line(depth, "}");
+                e
+            },
+            None => {
+                self.emit_range(next, t, ctx, depth + 1, None);
+                self.line(depth, "}");
+                t
+            },
+        }
+    }
+
+    // emits an unconditional jump
+    fn emit_jump(&mut self, jump: &Instruction, hi: usize, ctx: Option<Loop>, depth: usize) {
+        match (jump.jump_target(), ctx) {
+            (Some(Target::Static(t)), Some(ctx)) if t == ctx.head => {
+                // the jump back at the end of a loop is implied
+                if jump.next_addr() != hi {
+                    self.line(depth, "continue;");
+                }
+            },
+            (Some(Target::Static(t)), Some(ctx)) if t == ctx.exit => self.line(depth, "break;"),
+            (target, _) => {
+                let goto = self.goto(target, jump);
+                self.line(depth, &goto);
+            },
+        }
+    }
+
+    fn emit_instr(&mut self, instr: &Instruction, depth: usize) {
+        let imm = |pn: usize| instr.operands[pn].immediate();
+
+        let stmt = match instr.opcode {
+            1 | 2 => {
+                let (op, identity) = if instr.opcode == 1 { ("+", 0) } else { ("*", 1) };
+                let dst = self.write(instr, 2);
+                let (a, b) = (self.read(instr, 0), self.read(instr, 1));
+                // moves are written as an add of 0 or a multiply by 1
+                if imm(1) == Some(identity) {
+                    format!("{} = {};", dst, a)
+                } else if imm(0) == Some(identity) {
+                    format!("{} = {};", dst, b)
+                } else if instr.opcode == 2 && imm(1) == Some(-1) {
+                    format!("{} = -{};", dst, a)
+                } else {
+                    format!("{} = {} {} {};", dst, a, op, b)
+                }
+            },
+            3 => format!("{} = input();  // input at {}", self.write(instr, 0), instr.addr),
+            4 => format!("output({});  // output at {}", self.read(instr, 0), instr.addr),
+            7 | 8 => {
+                let op = if instr.opcode == 7 { "<" } else { "==" };
+                let dst = self.write(instr, 2);
+                format!("{} = {} {} {};", dst, self.read(instr, 0), op, self.read(instr, 1))
+            },
+            // a jump which is never taken
+            5 | 6 => return,
+            99 => "halt;".to_string(),
+            _ => unreachable!("only standard opcodes are decoded"),
+        };
+        self.line(depth, &stmt);
+    }
+
+    // Emits the code in [lo, hi), recovering loops and conditionals from the
+    // jumps between its instructions. Jumps which do not fit a structured
+    // construct are emitted as gotos. `skip_loop` is the head of a loop whose
+    // body is being emitted, which must not be detected as a loop again.
+    fn emit_range(&mut self, lo: usize, hi: usize, ctx: Option<Loop>, depth: usize, skip_loop: Option<usize>) {
+        let disasm = self.disasm;
+        let mut cursor = lo;
+
+        while let Some(addr) = self.next_addr(cursor, hi) {
+            if self.labels.contains(&addr) {
+                self.line(depth.saturating_sub(1), &format!("L{}:", addr));
+            }
+
+            if let Some(err) = disasm.errors.get(&addr) {
+                let writers = disasm.static_writes()
+                    .filter(|&(_, target)| target == addr)
+                    .map(|(instr, _)| instr.to_string())
+                    .collect::<Vec<_>>();
+                let mut note = format!("// {}: cannot decode: {}", addr, err);
+                if !writers.is_empty() {
+                    write!(note, " (overwritten by the instruction at {})", writers.join(", ")).unwrap();
+                }
+                self.line(depth, &note);
+
+                // a word written before it runs continues at the code which
+                // follows it, or jumps elsewhere
+                if let Some(after) = disasm.rewritten.get(&addr) {
+                    let follows = self.next_addr(addr + 1, hi);
+                    let after = after.iter()
+                        .map(|&next| if Some(next) == follows {
+                            next.to_string()
+                        } else {
+                            self.gotos.insert(next);
+                            format!("L{}", next)
+                        })
+                        .collect::<Vec<_>>();
+                    self.line(depth, &format!("// then continues at {}", after.join(" or ")));
+                }
+                cursor = addr + 1;
+                continue;
+            }
+
+            if skip_loop != Some(addr) {
+                if let Some(end) = self.loop_end(addr, hi) {
+                    self.emit_loop(addr, end, depth);
+                    cursor = end;
+                    continue;
+                }
+            }
+
+            let instr = &disasm.instrs[&addr];
+            if let Some((cond, jump)) = self.branch_at(addr) {
+                cursor = self.emit_branch(cond, jump, hi, ctx, depth);
+            } else if instr.is_jump() && !instr.falls_through() {
+                self.emit_jump(instr, hi, ctx, depth);
+                cursor = instr.next_addr();
+            } else {
+                self.emit_instr(instr, depth);
+                cursor = instr.next_addr();
+            }
+        }
+    }
+}
+
+/// Lifts a program into structured pseudo-code. Data addresses accessed in
+/// position mode become variables named after their address, jumps become
+/// `if`/`else` and `while` constructs where they fit one, and every input and
+/// output is annotated with its address. Jumps which fit no construct are
+/// shown as gotos, and operands which the program overwrites are shown as
+/// reads of raw memory `m[..]`. Code reached only through computed jumps is
+/// found by the same guessing as `Disassembly::explore`.
+pub fn decompile(memory: &[i64]) -> String {
+    // guesses are rejected if they reach undecodable code, other than jumps
+    // out of memory and words which are written before they run
+    let accept = |d: &Disassembly| d.errors.iter()
+        .all(|(addr, &err)| err == DecodeError::OutOfBounds || d.rewritten.contains_key(addr));
+    let disasm = Disassembly::explore(memory, &OpcodeTable::standard(), accept);
+
+    // the code is emitted once to find which addresses need labels, then
+    // again with the labels in place
+    let mut decompiler = Decompiler::new(&disasm, memory);
+    decompiler.emit_range(0, memory.len(), None, 1, None);
+
+    let mut labels = decompiler.gotos.clone();
+    labels.extend(disasm.roots.iter().filter(|&&root| root != 0));
+    let mut decompiler = Decompiler::new(&disasm, memory);
+    decompiler.labels = labels;
+    decompiler.emit_range(0, memory.len(), None, 1, None);
+
+    let mut out = String::new();
+    writeln!(out, "// decompiled from a {}-word Intcode program", memory.len()).unwrap();
+    for &root in disasm.roots.iter().filter(|&&root| root != 0) {
+        writeln!(out, "// L{} is a guessed target of a computed jump", root).unwrap();
+    }
+    writeln!(out).unwrap();
+    for &var in decompiler.vars.iter() {
+        writeln!(out, "var v{} = {};", var, memory[var]).unwrap();
+    }
+    if !decompiler.vars.is_empty() {
+        writeln!(out).unwrap();
+    }
+    writeln!(out, "program {{").unwrap();
+    out.push_str(&decompiler.out);
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::{compile, Intcode};
+    use crate::utils::PuzzleInput;
+
+    fn puzzle(day: usize) -> Vec<i64> {
+        Intcode::parse(PuzzleInput::new(day).next().unwrap())
+    }
+
+    #[test]
+    fn compiler_output() {
+        let memory = compile("
+            total = 0;
+            input x;
+            while x != 0 {
+                if x < 0 {
+                    total = total - x;
+                } else {
+                    total = total + x;
+                }
+                input x;
+            }
+            output total;
+        ").unwrap();
+        assert_eq!(decompile(&memory), "\
+// decompiled from a 51-word Intcode program
+
+var v47 = 0;
+var v48 = 0;
+var v49 = 0;
+
+program {
+    v47 = 0;
+    v48 = input();  // input at 4
+    loop {
+        v49 = v48 == 0;
+        if v49 != 0 { break; }
+        if v48 < 0 {
+            v49 = -v48;
+            v47 = v47 + v49;
+        } else {
+            v47 = v47 + v48;
+        }
+        v48 = input();  // input at 39
+    }
+    output(v47);  // output at 44
+    halt;
+}
+");
+    }
+
+    #[test]
+    fn rewritten_instruction() {
+        // the word at 6 is an add or a jump depending on the input
+        let text = decompile(&puzzle(5));
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[lines.iter().position(|l| l.contains("input at 0")).unwrap()..][..5], [
+            "    v225 = input();  // input at 0",
+            "    m[6] = v225 + m[6];",
+            "    // 6: cannot decode: unknown opcode 0 (overwritten by the instruction at 2)",
+            "    // then continues at 10 or L238",
+            "    output(0);  // output at 10",
+        ]);
+        assert!(!text.contains("var v6 "));
+        assert!(lines.contains(&"L238:"));
+        assert!(text.ends_with("    output(v223);  // output at 674\n    halt;\n}\n"));
+    }
+
+    #[test]
+    fn computed_jumps() {
+        // the phase setting selects one of the amplifier routines
+        let text = decompile(&puzzle(7));
+        assert!(text.contains("    goto *m[m[8]];\nL21:\n    v9 = input();  // input at 21\n"));
+        assert!(!text.contains("cannot decode"));
+        assert_eq!(text.matches("is a guessed target of a computed jump").count(), 11);
+    }
+}
@@ -0,0 +1,229 @@
+/*
+** src/types/intcode/diff.rs
+** comparison of two runs of an Intcode program
+*/
+
+use std::fmt;
+
+use crate::types::intcode::{Intcode, Status};
+
+/// An instruction executed by a program, as it was at the time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Executed {
+    pub addr: usize,
+    pub instr: i64,
+}
+
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.addr, self.instr)
+    }
+}
+
+impl Intcode {
+    /// Records every executed instruction, following the builder pattern.
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(vec![]);
+        self
+    }
+
+    /// the instructions executed so far, if tracing is enabled
+    pub fn trace(&self) -> Option<&[Executed]> {
+        self.trace.as_deref()
+    }
+}
+
+/// A position at which two sequences differ; a side is None if its sequence
+/// ends before the position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    pub index: usize,
+    pub left: Option<i64>,
+    pub right: Option<i64>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |val: Option<i64>| val.map_or("-".to_owned(), |val| val.to_string());
+        write!(f, "{}: {} vs {}", self.index, side(self.left), side(self.right))
+    }
+}
+
+// the positions at which two sequences differ
+fn differences(left: &[i64], right: &[i64]) -> Vec<Difference> {
+    (0..left.len().max(right.len()))
+        .map(|index| Difference { index, left: left.get(index).cloned(), right: right.get(index).cloned() })
+        .filter(|diff| diff.left != diff.right)
+        .collect()
+}
+
+/// The first instruction at which two traces differ; a side is None if its
+/// trace ends first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceDivergence {
+    pub step: usize,
+    pub left: Option<Executed>,
+    pub right: Option<Executed>,
+}
+
+/// The differences between two runs of a program
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunDiff {
+    /// addresses whose values differ, in order
+    pub memory: Vec<Difference>,
+    /// where the executed instructions diverge, if both runs were traced
+    pub trace: Option<TraceDivergence>,
+    /// outputs which differ, by their position in the output
+    pub outputs: Vec<Difference>,
+    pub status: (Status, Status),
+}
+
+impl RunDiff {
+    /// Compares two programs in their current state, which may be paused.
+    /// Outputs are taken from the programs' transcripts if they are
+    /// recording, and otherwise from their output queues; traces are only
+    /// compared if both programs are tracing.
+    pub fn between(left: &Intcode, right: &Intcode) -> Self {
+        let outputs = |prog: &Intcode| match prog.transcript() {
+            Some(transcript) => transcript.outputs().collect::<Vec<_>>(),
+            None => prog.output_iter().cloned().collect(),
+        };
+
+        let trace = match (left.trace(), right.trace()) {
+            (Some(l), Some(r)) => (0..l.len().max(r.len()))
+                .map(|step| (step, l.get(step).cloned(), r.get(step).cloned()))
+                .find(|(_, l, r)| l != r)
+                .map(|(step, left, right)| TraceDivergence { step, left, right }),
+            _ => None,
+        };
+
+        Self {
+            memory: differences(&left.memory, &right.memory),
+            trace,
+            outputs: differences(&outputs(left), &outputs(right)),
+            status: (left.status.clone(), right.status.clone()),
+        }
+    }
+
+    /// Runs a program once with each of two inputs, until each halts, faults
+    /// or runs out of input, and compares the two runs.
+    pub fn from_inputs(memory: &[i64], left: &[i64], right: &[i64]) -> Self {
+        let run = |input: &[i64]| {
+            let mut prog = Intcode::new(memory.to_vec()).with_trace().with_recording();
+            for &val in input {
+                prog.input(val);
+            }
+            prog.run();
+            prog
+        };
+        Self::between(&run(left), &run(right))
+    }
+
+    /// whether or not the two runs were the same
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.trace.is_none() && self.outputs.is_empty()
+            && self.status.0 == self.status.1
+    }
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "runs are identical");
+        }
+
+        if self.status.0 != self.status.1 {
+            writeln!(f, "status: {:?} vs {:?}", self.status.0, self.status.1)?;
+        }
+        if let Some(ref div) = self.trace {
+            let side = |exec: Option<Executed>| exec.map_or("end of trace".to_owned(), |e| e.to_string());
+            writeln!(f, "first divergent instruction at step {}: {} vs {}",
+                div.step, side(div.left), side(div.right))?;
+        }
+        if !self.outputs.is_empty() {
+            writeln!(f, "{} differing outputs:", self.outputs.len())?;
+            for diff in self.outputs.iter() {
+                writeln!(f, "    {}", diff)?;
+            }
+        }
+        if !self.memory.is_empty() {
+            writeln!(f, "{} differing addresses:", self.memory.len())?;
+            for diff in self.memory.iter() {
+                writeln!(f, "    {}", diff)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // outputs 0 if the input is 0, and 1 otherwise, jumping over a store
+    const JUMP: &[i64] = &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+
+    #[test]
+    fn identical_runs() {
+        let diff = RunDiff::from_inputs(JUMP, &[5], &[5]);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "runs are identical\n");
+    }
+
+    #[test]
+    fn differing_runs() {
+        let diff = RunDiff::from_inputs(JUMP, &[0], &[1]);
+        assert_eq!(diff, RunDiff {
+            memory: vec![
+                Difference { index: 3, left: Some(0), right: Some(1) },
+                Difference { index: 12, left: Some(0), right: Some(1) },
+            ],
+            trace: Some(TraceDivergence {
+                step: 2,
+                left: Some(Executed { addr: 5, instr: 1101 }),
+                right: Some(Executed { addr: 9, instr: 4 }),
+            }),
+            outputs: vec![Difference { index: 0, left: Some(0), right: Some(1) }],
+            status: (Status::Halted, Status::Halted),
+        });
+        assert_eq!(diff.to_string(), "\
+first divergent instruction at step 2: 5 (1101) vs 9 (4)
+1 differing outputs:
+    0: 0 vs 1
+2 differing addresses:
+    3: 0 vs 1
+    12: 0 vs 1
+");
+    }
+
+    #[test]
+    fn paused_and_untraced() {
+        // one program is still waiting for input; without traces, only
+        // outputs, memory and status are compared
+        let mut left = Intcode::new(JUMP.to_vec());
+        left.run();
+        let mut right = Intcode::new(JUMP.to_vec()).with_input(0);
+        right.run();
+
+        let diff = RunDiff::between(&left, &right);
+        assert_eq!(diff.trace, None);
+        assert_eq!(diff.outputs, vec![Difference { index: 0, left: None, right: Some(0) }]);
+        assert_eq!(diff.to_string(), "\
+status: Waiting vs Halted
+1 differing outputs:
+    0: - vs 0
+2 differing addresses:
+    3: -1 vs 0
+    12: 1 vs 0
+");
+
+        // a trace which ends first; an input which blocks has not executed
+        let diff = RunDiff::from_inputs(JUMP, &[], &[0]);
+        assert_eq!(diff.trace, Some(TraceDivergence {
+            step: 0,
+            left: None,
+            right: Some(Executed { addr: 0, instr: 3 }),
+        }));
+        assert!(diff.to_string().contains("at step 0: end of trace vs 0 (3)\n"));
+    }
+}
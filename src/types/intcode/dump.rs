@@ -0,0 +1,118 @@
+/*
+** src/types/intcode/dump.rs
+** formatted views of Intcode memory for debugging
+*/
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::types::intcode::Intcode;
+
+impl Intcode {
+    /// whether or not an address holds a different value than it did when
+    /// the program was loaded
+    pub fn is_modified(&self, addr: usize) -> bool {
+        self.loaded.get(addr) != self.memory.get(addr)
+    }
+
+    /// the addresses which have been modified since the program was loaded
+    pub fn modified(&self) -> impl Iterator<Item=usize> + '_ {
+        (0..self.memory.len()).filter(move |&addr| self.is_modified(addr))
+    }
+
+    /// A view of a range of memory which can be displayed; the range is
+    /// clamped to the end of memory
+    pub fn dump(&self, range: Range<usize>) -> Dump<'_> {
+        let end = range.end.min(self.memory.len());
+        Dump { prog: self, range: range.start.min(end)..end, columns: 8 }
+    }
+}
+
+/// A range of memory displayed in rows, each starting with the address of its
+/// first cell. The cell at the instruction pointer is enclosed in brackets and
+/// cells modified since the program was loaded are marked with '*'.
+pub struct Dump<'a> {
+    prog: &'a Intcode,
+    range: Range<usize>,
+    columns: usize,
+}
+
+impl Dump<'_> {
+    /// sets the number of cells in each row, 8 by default, following the
+    /// builder pattern
+    pub fn with_columns(mut self, columns: usize) -> Self {
+        self.columns = columns.max(1);
+        self
+    }
+}
+
+impl fmt::Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let memory = &self.prog.memory[self.range.clone()];
+        let addr_width = self.range.end.saturating_sub(1).to_string().len();
+        let width = memory.iter().map(|val| val.to_string().len()).max().unwrap_or(1);
+
+        for (row, cells) in memory.chunks(self.columns).enumerate() {
+            let start = self.range.start + row * self.columns;
+            let mut line = format!("{:>w$}:", start, w = addr_width);
+            for (i, val) in cells.iter().enumerate() {
+                let addr = start + i;
+                let (open, close) = match addr == self.prog.instr_ptr() {
+                    true => ('[', ']'),
+                    false => (' ', ' '),
+                };
+                let modified = if self.prog.is_modified(addr) { '*' } else { ' ' };
+                line += &format!(" {}{:>w$}{}{}", open, val, close, modified, w = width);
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stores 2 + 3 at 9, multiplies it by 10, then halts
+    const ARITH: &[i64] = &[1101, 2, 3, 9, 1002, 9, 10, 9, 99, 0, 0, 0];
+
+    #[test]
+    fn tracks_modified() {
+        let mut prog = Intcode::new(ARITH.to_vec());
+        assert_eq!(prog.modified().count(), 0);
+        prog.step();
+        assert!(prog.is_modified(9));
+        assert!(!prog.is_modified(100));
+        assert_eq!(prog.modified().collect::<Vec<_>>(), vec![9]);
+
+        // a cell written back to its loaded value is not modified
+        prog.memory[9] = 0;
+        assert_eq!(prog.modified().count(), 0);
+    }
+
+    #[test]
+    fn formats_rows() {
+        let mut prog = Intcode::new(ARITH.to_vec());
+        prog.step();
+        assert_eq!(prog.dump(0..12).with_columns(4).to_string(), concat!(
+            " 0:  1101       2       3       9\n",
+            " 4: [1002]      9      10       9\n",
+            " 8:    99       5 *     0       0\n",
+        ));
+
+        prog.run();
+        assert_eq!(prog.dump(0..12).to_string(), concat!(
+            " 0:  1101       2       3       9    1002       9      10       9\n",
+            " 8: [  99]     50 *     0       0\n",
+        ));
+        assert_eq!(prog.dump(8..10).with_columns(0).to_string(), "8: [99]\n9:  50 *\n");
+    }
+
+    #[test]
+    fn clamps_range() {
+        let prog = Intcode::new(ARITH.to_vec());
+        assert_eq!(prog.dump(10..100).to_string(), "10:  0    0\n");
+        assert_eq!(prog.dump(20..30).to_string(), "");
+    }
+}
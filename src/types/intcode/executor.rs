@@ -0,0 +1,269 @@
+/*
+** src/types/intcode/executor.rs
+** running many Intcode programs as futures on a single thread
+*/
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::types::intcode::{Intcode, Yield};
+
+#[derive(Default)]
+struct PipeInner {
+    values: VecDeque<i64>,
+    closed: bool,
+    // the task waiting to receive from the pipe
+    waker: Option<Waker>,
+}
+
+/// A queue of values from one program to another. Clones share the queue.
+/// The receiving program is woken whenever a value is sent or the pipe is
+/// closed.
+#[derive(Clone, Default)]
+pub struct Pipe {
+    inner: Rc<RefCell<PipeInner>>,
+}
+
+impl Pipe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, val: i64) {
+        let mut inner = self.inner.borrow_mut();
+        inner.values.push_back(val);
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Marks that no more values will be sent; a program waiting on an empty
+    /// closed pipe finishes
+    pub fn close(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.closed = true;
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub fn recv(&self) -> Option<i64> {
+        self.inner.borrow_mut().values.pop_front()
+    }
+}
+
+/// A program running as a future, reading its input from one pipe and
+/// writing its output to another. It yields to other tasks whenever it needs
+/// input which has not arrived, and after every value it outputs. Once it
+/// halts, faults, or waits for input on a closed pipe, its output pipe is
+/// closed and the future resolves to the program, so that closing the input
+/// to a chain of programs shuts down each in turn.
+pub struct Running {
+    prog: Option<Intcode>,
+    input: Pipe,
+    output: Pipe,
+}
+
+impl Intcode {
+    /// turns the program into a future which communicates through pipes
+    pub fn into_future(self, input: Pipe, output: Pipe) -> Running {
+        Running { prog: Some(self), input, output }
+    }
+}
+
+impl Future for Running {
+    type Output = Intcode;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Intcode> {
+        let this = &mut *self;
+        let prog = this.prog.as_mut().expect("program polled after finishing");
+        loop {
+            match prog.poll() {
+                Yield::Output(val) => {
+                    this.output.send(val);
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                },
+                Yield::NeedInput => match this.input.recv() {
+                    Some(val) => prog.input(val),
+                    None if this.input.inner.borrow().closed => break,
+                    None => {
+                        this.input.inner.borrow_mut().waker = Some(cx.waker().clone());
+                        return Poll::Pending;
+                    },
+                },
+                Yield::Halted | Yield::Faulted(_) => break,
+            }
+        }
+        this.output.close();
+        Poll::Ready(this.prog.take().unwrap())
+    }
+}
+
+// wakes a task by queueing it to be polled
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut ready = self.ready.lock().unwrap();
+        if !ready.contains(&self.id) {
+            ready.push_back(self.id);
+        }
+    }
+}
+
+/// The eventual result of a spawned future
+pub struct Task<T> {
+    result: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> Task<T> {
+    /// the result, if the future has finished and it has not been taken
+    pub fn take(&self) -> Option<T> {
+        self.result.borrow_mut().take()
+    }
+}
+
+type LocalFuture = Pin<Box<dyn Future<Output=()>>>;
+
+/// A minimal single-threaded executor. Tasks are polled in the order they
+/// are woken.
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<LocalFuture>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F>(&mut self, fut: F) -> Task<F::Output>
+    where F: Future + 'static
+    {
+        let result = Rc::new(RefCell::new(None));
+        let slot = result.clone();
+        let task = async move {
+            let val = fut.await;
+            *slot.borrow_mut() = Some(val);
+        };
+
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(task)));
+        Task { result }
+    }
+
+    /// Polls tasks until none are ready, returning the number which have not
+    /// finished; these are blocked waiting for input which was never sent.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let id = match self.ready.lock().unwrap().pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let fut = match self.tasks[id] {
+                Some(ref mut fut) => fut,
+                None => continue,
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: self.ready.clone() }));
+            if fut.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                self.tasks[id] = None;
+            }
+        }
+        self.tasks.iter().filter(|task| task.is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::Status;
+
+    // day 7: amplifiers connected in a feedback loop
+    const AMPLIFIER_LOOP: &[i64] = &[
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4,
+        27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5,
+    ];
+
+    // outputs each input, forever
+    const ECHO: &[i64] = &[3, 7, 4, 7, 1105, 1, 0, 0];
+
+    #[test]
+    fn feedback_loop() {
+        let phases = [9, 8, 7, 6, 5];
+        let pipes = (0..phases.len()).map(|_| Pipe::new()).collect::<Vec<_>>();
+        pipes[0].send(phases[0]);
+        pipes[0].send(0);
+
+        let mut exec = Executor::new();
+        let tasks = phases.iter()
+            .enumerate()
+            .map(|(n, &phase)| {
+                if n > 0 {
+                    pipes[n].send(phase);
+                }
+                let prog = Intcode::new(AMPLIFIER_LOOP.to_vec());
+                exec.spawn(prog.into_future(pipes[n].clone(), pipes[(n + 1) % phases.len()].clone()))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(exec.run(), 0);
+        for task in tasks.iter() {
+            assert_eq!(task.take().unwrap().status, Status::Halted);
+        }
+        // the first amplifier halted before the final signal was fed back
+        assert_eq!(pipes[0].recv(), Some(139629729));
+        assert_eq!(pipes[0].recv(), None);
+    }
+
+    #[test]
+    fn shutdown_passes_down_chain() {
+        let pipes = (0..4).map(|_| Pipe::new()).collect::<Vec<_>>();
+        let mut exec = Executor::new();
+        let tasks = (0..3)
+            .map(|n| {
+                let prog = Intcode::new(ECHO.to_vec());
+                exec.spawn(prog.into_future(pipes[n].clone(), pipes[n + 1].clone()))
+            })
+            .collect::<Vec<_>>();
+
+        pipes[0].send(1);
+        pipes[0].send(2);
+        assert_eq!(exec.run(), 3);
+        assert!(tasks.iter().all(|task| task.take().is_none()));
+
+        // closing the first input finishes every program, in turn
+        pipes[0].close();
+        assert_eq!(exec.run(), 0);
+        for task in tasks.iter() {
+            assert_eq!(task.take().unwrap().status, Status::Waiting);
+        }
+        assert_eq!((pipes[3].recv(), pipes[3].recv(), pipes[3].recv()), (Some(1), Some(2), None));
+        assert!(pipes[3].inner.borrow().closed);
+    }
+
+    #[test]
+    fn fault_closes_output() {
+        let (input, output) = (Pipe::new(), Pipe::new());
+        let mut exec = Executor::new();
+        let task = exec.spawn(Intcode::new(vec![104, 5, 42]).into_future(input, output.clone()));
+        assert_eq!(exec.run(), 0);
+        assert!(matches!(task.take().unwrap().status, Status::Faulted(_)));
+        assert_eq!(output.recv(), Some(5));
+        assert!(output.inner.borrow().closed);
+    }
+}
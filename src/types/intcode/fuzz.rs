@@ -0,0 +1,291 @@
+/*
+** src/types/intcode/fuzz.rs
+** differential fuzzing of the Intcode interpreter against a reference
+*/
+
+use crate::types::intcode::{Intcode, Status};
+
+// xorshift64* generator, seeded deterministically so failures reproduce
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must never be zero
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next() % (hi - lo + 1) as u64) as i64
+    }
+
+    fn chance(&mut self, pct: usize) -> bool {
+        self.below(100) < pct
+    }
+}
+
+// a generated program along with the input it is run with
+struct Case {
+    memory: Vec<i64>,
+    input: Vec<i64>,
+}
+
+// Generates a random valid program: a code region of instructions ending in a
+// halt, followed by a data region. Every opcode and parameter mode is valid,
+// all addresses are in bounds, writes only target the data region (so the
+// code can never be corrupted into an invalid opcode) and jumps only target
+// the start of an instruction.
+fn generate(rng: &mut Rng) -> Case {
+    let n_instrs = 1 + rng.below(24);
+    let n_data = 1 + rng.below(16);
+
+    // choose the opcodes up front so that instruction addresses are known
+    let opcodes = (0..n_instrs)
+        .map(|_| 1 + rng.below(8) as i64)
+        .collect::<Vec<_>>();
+    let mut starts = vec![];
+    let mut code_len = 0;
+    for &opcode in opcodes.iter() {
+        starts.push(code_len as i64);
+        code_len += match opcode {
+            1 | 2 | 7 | 8 => 4,
+            3 | 4 => 2,
+            _ => 3,
+        };
+    }
+    // the final halt
+    starts.push(code_len as i64);
+    code_len += 1;
+
+    let mem_len = code_len + n_data;
+    let data_addr = |rng: &mut Rng| (code_len + rng.below(n_data)) as i64;
+
+    let mut memory = Vec::with_capacity(mem_len);
+    for opcode in opcodes {
+        let mut modes = [0; 2];
+        let mut params = vec![];
+
+        // read parameters are either immediate values or any address
+        let read_param = |rng: &mut Rng, modes: &mut [i64; 2], pn: usize| {
+            if rng.chance(50) {
+                modes[pn] = 1;
+                rng.range(-20, 20)
+            } else {
+                rng.below(mem_len) as i64
+            }
+        };
+
+        match opcode {
+            1 | 2 | 7 | 8 => {
+                params.push(read_param(rng, &mut modes, 0));
+                params.push(read_param(rng, &mut modes, 1));
+                params.push(data_addr(rng));
+            },
+            3 => params.push(data_addr(rng)),
+            4 => params.push(read_param(rng, &mut modes, 0)),
+            _ => {
+                params.push(read_param(rng, &mut modes, 0));
+                // jump targets are immediate instruction addresses
+                modes[1] = 1;
+                params.push(starts[rng.below(starts.len())]);
+            },
+        }
+
+        memory.push(opcode + 100 * modes[0] + 1000 * modes[1]);
+        memory.extend(params);
+    }
+    memory.push(99);
+    memory.extend((0..n_data).map(|_| rng.range(-50, 50)));
+
+    let input = (0..rng.below(6)).map(|_| rng.range(-100, 100)).collect();
+
+    Case { memory, input }
+}
+
+#[derive(Debug, PartialEq)]
+enum RefStatus {
+    Running,
+    Waiting,
+    Halted,
+    Overflow,
+}
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    memory: Vec<i64>,
+    output: Vec<i64>,
+    status: RefStatus,
+    steps: u64,
+}
+
+// reads parameter pn of the instruction at ip, according to its mode
+fn read(mem: &[i64], ip: usize, pn: usize) -> i64 {
+    let param = mem[ip + pn];
+    if (mem[ip] / 10i64.pow(pn as u32 + 1)) % 10 == 1 {
+        param
+    } else {
+        mem[param as usize]
+    }
+}
+
+// writes to the address given by parameter pn of the instruction at ip
+fn write(mem: &mut [i64], ip: usize, pn: usize, val: i64) {
+    let addr = mem[ip + pn] as usize;
+    mem[addr] = val;
+}
+
+// a deliberately simple interpreter, written independently of Intcode::run
+fn reference_run(case: &Case, max_steps: u64) -> Outcome {
+    let mut mem = case.memory.clone();
+    let mut input = case.input.iter();
+    let mut output = vec![];
+    let mut ip = 0;
+    let mut steps = 0;
+
+    let status = loop {
+        if steps == max_steps {
+            break RefStatus::Running;
+        }
+
+        match mem[ip] % 100 {
+            op @ 1 | op @ 2 => {
+                let res = if op == 1 {
+                    read(&mem, ip, 1).checked_add(read(&mem, ip, 2))
+                } else {
+                    read(&mem, ip, 1).checked_mul(read(&mem, ip, 2))
+                };
+                match res {
+                    Some(res) => write(&mut mem, ip, 3, res),
+                    None => break RefStatus::Overflow,
+                }
+                ip += 4;
+            },
+            3 => match input.next() {
+                Some(&val) => {
+                    write(&mut mem, ip, 1, val);
+                    ip += 2;
+                },
+                None => break RefStatus::Waiting,
+            },
+            4 => {
+                output.push(read(&mem, ip, 1));
+                ip += 2;
+            },
+            5 => ip = if read(&mem, ip, 1) != 0 { read(&mem, ip, 2) as usize } else { ip + 3 },
+            6 => ip = if read(&mem, ip, 1) == 0 { read(&mem, ip, 2) as usize } else { ip + 3 },
+            7 => {
+                let res = (read(&mem, ip, 1) < read(&mem, ip, 2)) as i64;
+                write(&mut mem, ip, 3, res);
+                ip += 4;
+            },
+            8 => {
+                let res = (read(&mem, ip, 1) == read(&mem, ip, 2)) as i64;
+                write(&mut mem, ip, 3, res);
+                ip += 4;
+            },
+            99 => {
+                steps += 1;
+                break RefStatus::Halted;
+            },
+            op => panic!("generator produced opcode {}", op),
+        }
+        steps += 1;
+    };
+
+    Outcome { memory: mem, output, status, steps }
+}
+
+fn vm_run(case: &Case, max_steps: u64) -> Outcome {
+    let mut prog = Intcode::new(case.memory.clone());
+    for &val in case.input.iter() {
+        prog.input(val);
+    }
+
+    while prog.steps() < max_steps {
+        prog.step();
+        if prog.status != Status::Running {
+            break;
+        }
+    }
+
+    let status = match prog.status {
+        Status::Initial | Status::Running => RefStatus::Running,
+        Status::Waiting => RefStatus::Waiting,
+        Status::Halted => RefStatus::Halted,
+        Status::Faulted(_) => RefStatus::Overflow,
+    };
+
+    Outcome {
+        output: prog.output_iter().cloned().collect(),
+        memory: prog.memory,
+        status,
+        steps: prog.steps,
+    }
+}
+
+fn differential(seed: u64, cases: usize, max_steps: u64) {
+    let mut rng = Rng::new(seed);
+    for n in 0..cases {
+        let case = generate(&mut rng);
+        let expected = reference_run(&case, max_steps);
+        let actual = vm_run(&case, max_steps);
+
+        assert_eq!(
+            expected, actual,
+            "case {} of seed {} diverged\nprogram: {:?}\ninput: {:?}",
+            n, seed, case.memory, case.input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_short_runs() {
+        differential(2019, 5000, 64);
+    }
+
+    #[test]
+    fn matches_reference_long_runs() {
+        differential(0xdecaf, 2000, 10_000);
+    }
+
+    #[test]
+    fn rewind_restores_initial_state() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let case = generate(&mut rng);
+            let expected = reference_run(&case, 256);
+
+            let mut prog = Intcode::new(case.memory.clone()).with_history(256);
+            for &val in case.input.iter() {
+                prog.input(val);
+            }
+            while prog.steps() < 256 && matches!(prog.status, Status::Initial | Status::Running) {
+                prog.step();
+            }
+
+            assert!(prog.rewind_to(0));
+            assert_eq!(prog.memory, case.memory);
+            assert_eq!(prog.output_iter().count(), 0);
+
+            // replaying after the rewind reproduces the same run
+            while prog.steps() < 256 && matches!(prog.status, Status::Initial | Status::Running) {
+                prog.step();
+            }
+            assert_eq!(prog.memory, expected.memory);
+            assert_eq!(prog.output_iter().cloned().collect::<Vec<_>>(), expected.output);
+        }
+    }
+}
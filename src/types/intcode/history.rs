@@ -0,0 +1,279 @@
+/*
+** src/types/intcode/history.rs
+*/
+
+use std::collections::VecDeque;
+
+use crate::types::intcode::{BigInt, Intcode, Status};
+
+// a single side-effect of an instruction, holding what is needed to undo it
+pub(super) enum Change {
+    // memory write, with the previous value and its exact value if it was
+    // too large for an i64
+    Write { addr: usize, old: i64, wide: Option<BigInt> },
+    Input(i64),  // value consumed from the input queue
+    Output,      // value pushed onto the output queue
+}
+
+// the state of the program before an instruction was executed, along with
+// the changes the instruction made; an instruction which faulted partway
+// through is recorded but was not counted as executed
+struct Record {
+    instr_ptr: usize,
+    status: Status,
+    changes: Vec<Change>,
+    executed: bool,
+}
+
+/// An undo log of executed and faulted instructions, holding at most
+/// `capacity` of the most recent instructions
+pub(super) struct History {
+    records: VecDeque<Record>,
+    capacity: usize,
+    pending: Option<Record>,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+            pending: None,
+        }
+    }
+
+    // starts recording the instruction at the given instruction pointer
+    pub(super) fn begin(&mut self, instr_ptr: usize, status: Status) {
+        self.pending = Some(Record { instr_ptr, status, changes: vec![], executed: false });
+    }
+
+    pub(super) fn record(&mut self, change: Change) {
+        if let Some(ref mut record) = self.pending {
+            record.changes.push(change);
+        }
+    }
+
+    // finishes recording the current instruction, dropping the oldest record
+    // if the log is full; instructions which neither executed nor faulted,
+    // such as one blocked on input, are discarded
+    pub(super) fn commit(&mut self, executed: bool, faulted: bool) {
+        if let Some(mut record) = self.pending.take() {
+            if !(executed || faulted) || self.capacity == 0 {
+                return;
+            }
+            record.executed = executed;
+            if self.records.len() == self.capacity {
+                self.records.pop_front();
+            }
+            self.records.push_back(record);
+        }
+    }
+}
+
+impl Intcode {
+    /// Enables time-travel, following the builder pattern. The effects of the
+    /// last `capacity` executed instructions are recorded so that they can be
+    /// undone with `Intcode::step_back` and `Intcode::rewind_to`.
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = Some(History::new(capacity));
+        self
+    }
+
+    /// the number of instructions which can currently be undone, including
+    /// one which faulted
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| history.records.len())
+    }
+
+    /// Undoes the most recently executed instruction, restoring memory, the
+    /// instruction pointer, the status and the input/output queues, and
+    /// removing its events from the transcript if recording. Outputs which
+    /// have already been taken from the output queue are not restored. The
+    /// instruction is also removed from the trace if tracing. An instruction
+    /// which faulted partway through is undone first, leaving the program as
+    /// it was before the instruction ran.
+    /// Returns false if there is no recorded instruction to undo.
+    pub fn step_back(&mut self) -> bool {
+        let record = match self.history.as_mut().and_then(|h| h.records.pop_back()) {
+            Some(record) => record,
+            None => return false,
+        };
+
+        for change in record.changes.into_iter().rev() {
+            match change {
+                Change::Write { addr, old, wide } => {
+                    self.memory[addr] = old;
+                    match wide {
+                        Some(val) => self.wide.insert(addr, val),
+                        None => self.wide.remove(&addr),
+                    };
+                },
+                Change::Input(input) => {
+                    self.input.push_front(input);
+                    if let Some(ref mut transcript) = self.transcript {
+                        transcript.pop();
+                    }
+                },
+                Change::Output => {
+                    // the output queue is FIFO, so if it is non-empty the
+                    // most recent output has not yet been consumed
+                    self.output.pop_back();
+                    if let Some(ref mut transcript) = self.transcript {
+                        transcript.pop();
+                    }
+                },
+            }
+        }
+
+        if record.executed {
+            if let Some(ref mut trace) = self.trace {
+                trace.pop();
+            }
+            self.steps -= 1;
+        }
+        self.instr_ptr = record.instr_ptr;
+        self.status = record.status;
+        true
+    }
+
+    /// Undoes instructions until `steps` instructions have been executed.
+    /// Returns false if the history does not reach back far enough, in which
+    /// case the program is left unchanged.
+    pub fn rewind_to(&mut self, steps: u64) -> bool {
+        let recorded = self.history.as_ref()
+            .map_or(0, |history| history.records.iter().filter(|record| record.executed).count());
+        if self.steps.saturating_sub(steps) > recorded as u64 {
+            return false;
+        }
+        while self.steps > steps {
+            if !self.step_back() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::{Arithmetic, Event, Fault, Flow, Opcode, Param, Protection};
+
+    // adds each input to a running total, which it outputs, halting once the
+    // total is 0
+    const TOTAL: &[i64] = &[3, 13, 1, 13, 14, 14, 4, 14, 1005, 14, 0, 99, 0, 0, 0];
+
+    // opcode 20: takes an input, outputs it, then stores it at the address
+    // given by its parameter, which may turn out to be read-only
+    fn echo_store(vm: &mut Intcode, args: &[i64]) -> Flow {
+        match vm.pop_input() {
+            Some(val) => {
+                vm.push_output(val);
+                vm.set(args[0] as usize, val);
+                Flow::Next
+            },
+            None => Flow::Wait,
+        }
+    }
+
+    const ECHO_STORE: Opcode = Opcode { name: "echo", params: &[Param::Read], exec: echo_store };
+
+    fn total(input: &[i64]) -> Intcode {
+        let mut prog = Intcode::new(TOTAL.to_vec()).with_history(100).with_recording();
+        for &val in input {
+            prog.input(val);
+        }
+        prog
+    }
+
+    #[test]
+    fn evicts_oldest_steps() {
+        let mut prog = Intcode::new(TOTAL.to_vec()).with_history(3).with_input(3).with_input(4);
+        prog.run();
+        assert_eq!((prog.steps(), prog.history_len()), (8, 3));
+
+        // the history does not reach back to step 4
+        let memory = prog.memory.clone();
+        assert!(!prog.rewind_to(4));
+        assert_eq!((prog.steps(), prog.history_len()), (8, 3));
+        assert_eq!((prog.instr_ptr(), &prog.status), (0, &Status::Waiting));
+        assert_eq!(prog.memory, memory);
+
+        assert!(prog.rewind_to(5));
+        assert_eq!((prog.steps(), prog.history_len(), prog.instr_ptr()), (5, 0, 2));
+        assert!(!prog.step_back());
+
+        // nothing is recorded without a capacity
+        let mut prog = Intcode::new(TOTAL.to_vec()).with_history(0).with_input(3);
+        prog.run();
+        assert!(!prog.step_back());
+        assert!(prog.rewind_to(prog.steps()));
+    }
+
+    #[test]
+    fn restores_queues() {
+        let mut prog = total(&[3, 4]);
+        prog.run();
+        let transcript = prog.transcript().unwrap().clone();
+        assert_eq!(prog.output_iter().cloned().collect::<Vec<_>>(), vec![3, 7]);
+
+        // back over the second output, then the second input
+        assert!(prog.rewind_to(5));
+        assert_eq!(prog.output_iter().cloned().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(prog.transcript().unwrap().events(), &[Event::Input(3), Event::Output(3), Event::Input(4)]);
+        assert!(prog.rewind_to(4));
+        assert_eq!(prog.input.iter().cloned().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(prog.transcript().unwrap().events(), &[Event::Input(3), Event::Output(3)]);
+        assert_eq!(prog.memory[13..], [3, 3]);
+
+        // outputs already taken are not restored
+        assert_eq!(prog.output(), Some(3));
+        assert!(prog.rewind_to(0));
+        assert_eq!(prog.output(), None);
+        assert_eq!(prog.input.iter().cloned().collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(prog.memory, TOTAL);
+
+        prog.run();
+        assert_eq!(prog.transcript(), Some(&transcript));
+    }
+
+    #[test]
+    fn restores_wide_cells() {
+        // squares the value at 9 in place, then overwrites it with 3
+        let mut prog = Intcode::new(vec![2, 9, 9, 9, 1101, 1, 2, 9, 99, 0])
+            .with_arithmetic(Arithmetic::BigInt)
+            .with_history(4);
+        let small = "10000000000".parse::<BigInt>().unwrap();
+        prog.set_exact(9, small.clone());
+        prog.run();
+        assert_eq!(prog.exact(9), BigInt::from(3));
+
+        assert!(prog.step_back() && prog.step_back());
+        assert_eq!(prog.exact(9).to_string(), "100000000000000000000");
+        assert!(prog.step_back());
+        assert_eq!(prog.exact(9), small);
+        assert_eq!(prog.memory[9], 10000000000);
+    }
+
+    #[test]
+    fn undoes_faulted_instructions() {
+        let mut prog = Intcode::new(vec![120, 0, 99])
+            .with_opcode(20, ECHO_STORE)
+            .with_protection(0..3, Protection::ReadOnly)
+            .with_history(4)
+            .with_recording()
+            .with_input(5);
+        prog.run();
+        assert_eq!(prog.status, Status::Faulted(Fault::WriteProtected { instr_ptr: 0, instr: 120, addr: 0 }));
+        assert_eq!((prog.steps(), prog.history_len()), (0, 1));
+        assert_eq!(prog.output_iter().cloned().collect::<Vec<_>>(), vec![5]);
+
+        // the input and output are undone along with the fault
+        assert!(prog.step_back());
+        assert_eq!((prog.steps(), prog.instr_ptr(), &prog.status), (0, 0, &Status::Initial));
+        assert_eq!(prog.input.iter().cloned().collect::<Vec<_>>(), vec![5]);
+        assert_eq!(prog.output(), None);
+        assert_eq!(prog.transcript().unwrap().events(), &[]);
+        assert!(!prog.step_back());
+    }
+}
@@ -0,0 +1,159 @@
+/*
+** src/types/intcode/load.rs
+*/
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, prelude::*};
+use std::path::Path;
+
+use crate::types::intcode::Intcode;
+
+/// Errors produced when loading an Intcode program
+#[derive(Debug)]
+pub enum LoadError {
+    /// the underlying reader failed
+    Io(io::Error),
+    /// a value which is not an integer, at the given byte offset
+    InvalidValue { offset: usize, token: String },
+    /// a comma which does not follow a value, at the given byte offset
+    MissingValue { offset: usize },
+    /// the program contains no values
+    Empty,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::InvalidValue { offset, token } => {
+                write!(f, "byte {}: invalid value {:?}", offset, token)
+            },
+            LoadError::MissingValue { offset } => {
+                write!(f, "byte {}: missing value before comma", offset)
+            },
+            LoadError::Empty => write!(f, "program is empty"),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl Intcode {
+    /// Parses the text of a program. Values are separated by commas or line
+    /// breaks and may be surrounded by whitespace, and a trailing comma is
+    /// allowed. Lines starting with '#' are comments. Errors give the byte
+    /// offset of the offending token in the text.
+    pub fn parse_program(text: &str) -> Result<Vec<i64>, LoadError> {
+        let mut memory = vec![];
+        // whether a value is required before the next comma
+        let mut need_value = true;
+        let mut offset = 0;
+
+        for line in text.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            // byte offset and text of each comma-separated token in the line
+            let mut pos = start;
+            for token in line.split(',') {
+                let trimmed = token.trim();
+                let token_offset = pos + (token.len() - token.trim_start().len());
+                let comma = pos + token.len();
+                pos = comma + 1;
+
+                if !trimmed.is_empty() {
+                    let val = trimmed.parse::<i64>().map_err(|_| LoadError::InvalidValue {
+                        offset: token_offset,
+                        token: trimmed.to_owned(),
+                    })?;
+                    memory.push(val);
+                    need_value = false;
+                }
+
+                // every token but the last ends in a comma
+                if comma < offset {
+                    if need_value {
+                        return Err(LoadError::MissingValue { offset: comma });
+                    }
+                    need_value = true;
+                }
+            }
+        }
+
+        if memory.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        Ok(memory)
+    }
+
+    /// reads a program from a reader, as with `Intcode::parse_program`
+    pub fn read_program<R: Read>(mut r: R) -> Result<Vec<i64>, LoadError> {
+        let mut text = String::new();
+        r.read_to_string(&mut text)?;
+        Self::parse_program(&text)
+    }
+
+    /// reads a program from a file, as with `Intcode::parse_program`
+    pub fn read_program_file<P: AsRef<Path>>(path: P) -> Result<Vec<i64>, LoadError> {
+        Self::read_program(File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let parse = |text| Intcode::parse_program(text).unwrap();
+        assert_eq!(parse("1,9,10,3,2,3,11,0,99,30,40,50"), vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(parse("  1 ,\t-2,\r\n3  \n"), vec![1, -2, 3]);
+        assert_eq!(parse("1,2,\n3,4,\n"), vec![1, 2, 3, 4]);
+        assert_eq!(parse("1\n2\n3"), vec![1, 2, 3]);
+        assert_eq!(parse("# a comment, with commas\n1,2\n  # indented\n99\n"), vec![1, 2, 99]);
+    }
+
+    #[test]
+    fn reports_offsets() {
+        let parse = |text| Intcode::parse_program(text).unwrap_err();
+
+        match parse("1,2,,3") {
+            LoadError::MissingValue { offset } => assert_eq!(offset, 4),
+            err => panic!("unexpected error: {}", err),
+        }
+        match parse("# header\n,1") {
+            LoadError::MissingValue { offset } => assert_eq!(offset, 9),
+            err => panic!("unexpected error: {}", err),
+        }
+        match parse("1,2,\n, 3") {
+            LoadError::MissingValue { offset } => assert_eq!(offset, 5),
+            err => panic!("unexpected error: {}", err),
+        }
+
+        let err = parse("1, 2,\n  x3 ,4");
+        assert_eq!(err.to_string(), "byte 8: invalid value \"x3\"");
+        match err {
+            LoadError::InvalidValue { offset, token } => assert_eq!((offset, token.as_str()), (8, "x3")),
+            err => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(parse("1 2").to_string(), "byte 0: invalid value \"1 2\"");
+        assert_eq!(parse("99999999999999999999").to_string(), "byte 0: invalid value \"99999999999999999999\"");
+
+        assert!(matches!(parse(""), LoadError::Empty));
+        assert!(matches!(parse(" \n# only a comment\n"), LoadError::Empty));
+    }
+
+    #[test]
+    fn reads_program() {
+        assert_eq!(Intcode::read_program(&b"3,0,4,0,99\n"[..]).unwrap(), vec![3, 0, 4, 0, 99]);
+        assert!(matches!(Intcode::read_program_file("no/such/program"), Err(LoadError::Io(_))));
+    }
+}
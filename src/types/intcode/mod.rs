@@ -0,0 +1,564 @@
+/*
+** src/types/intcode/mod.rs
+*/
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+pub mod analysis;
+pub mod ascii;
+mod bigint;
+mod compile;
+#[cfg(test)]
+mod conformance;
+mod decompile;
+mod diff;
+mod dump;
+mod executor;
+#[cfg(test)]
+mod fuzz;
+mod history;
+mod load;
+mod network;
+mod opcode;
+mod optimize;
+mod phase;
+mod poll;
+mod protect;
+mod schedule;
+mod scheduler;
+mod state;
+mod threaded;
+mod transcript;
+mod transpile;
+mod validate;
+
+pub use ascii::{AsciiError, AsciiProgram, Grid};
+pub use bigint::{BigInt, ParseBigIntError};
+pub use compile::{compile, CompileError};
+pub use decompile::decompile;
+pub use diff::{Difference, Executed, RunDiff, TraceDivergence};
+pub use dump::Dump;
+pub use executor::{Executor, Pipe, Running, Task};
+use history::{Change, History};
+pub use load::LoadError;
+pub use network::{Network, Packet, Route};
+pub use opcode::{Flow, Opcode, OpcodeTable, Param};
+pub use optimize::{optimize, OptimizeError, Optimized};
+pub use phase::{PhaseSearch, PhaseSetting};
+pub use poll::Yield;
+pub use protect::Protection;
+pub use schedule::{ChainOutcome, ChainReport, Schedule};
+pub use scheduler::{Pid, ProcessState, Scheduler, SchedulerOutcome};
+pub use state::StateError;
+pub use threaded::NetworkError;
+pub use transcript::{Divergence, Event, Observed, Transcript, TranscriptError};
+pub use transpile::{transpile, TranspileError};
+pub use validate::{validate, Diagnostic, Problem, Severity};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Initial,         // program has not yet started
+    Running,         // program running
+    Waiting,         // program is waiting for input
+    Halted,          // program has halted
+    Faulted(Fault),  // program was stopped by an execution fault
+}
+
+/// An execution fault which stops an Intcode program without panicking
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// an add or multiply overflowed under checked arithmetic
+    Overflow { instr_ptr: usize, opcode: i64, op1: i64, op2: i64 },
+    /// the instruction has no definition in the opcode table
+    UnknownOpcode { instr_ptr: usize, opcode: i64 },
+    /// the instruction has a parameter mode which is not 0 or 1
+    InvalidMode { instr_ptr: usize, instr: i64 },
+    /// the instruction writes to a read-only address
+    WriteProtected { instr_ptr: usize, instr: i64, addr: usize },
+    /// the instruction is at a non-executable address
+    NotExecutable { instr_ptr: usize, instr: i64 },
+    /// under big-integer arithmetic, the instruction outputs or uses as an
+    /// address a value too large for an i64
+    TooLarge { instr_ptr: usize, instr: i64 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Overflow { instr_ptr, opcode, op1, op2 } => {
+                let op = if *opcode == 1 { "+" } else { "*" };
+                write!(f, "{}: arithmetic overflow in {} {} {}", instr_ptr, op1, op, op2)
+            },
+            Fault::UnknownOpcode { instr_ptr, opcode } => {
+                write!(f, "{}: unknown opcode {}", instr_ptr, opcode)
+            },
+            Fault::InvalidMode { instr_ptr, instr } => {
+                write!(f, "{}: invalid parameter mode in instruction {}", instr_ptr, instr)
+            },
+            Fault::WriteProtected { instr_ptr, instr, addr } => {
+                write!(f, "{}: instruction {} writes to read-only address {}", instr_ptr, instr, addr)
+            },
+            Fault::NotExecutable { instr_ptr, instr } => {
+                write!(f, "{}: instruction {} is at a non-executable address", instr_ptr, instr)
+            },
+            Fault::TooLarge { instr_ptr, instr } => {
+                write!(f, "{}: instruction {} uses a value too large for an i64", instr_ptr, instr)
+            },
+        }
+    }
+}
+
+/// Selects how add and multiply handle results which overflow an i64, so that
+/// programs behave the same in debug and release builds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arithmetic {
+    Checked,     // overflow faults the program
+    Wrapping,    // overflow wraps around at the boundary
+    Saturating,  // overflow clamps to i64::MIN or i64::MAX
+    BigInt,      // results are exact; see `Intcode::exact`
+}
+
+impl Arithmetic {
+    /// adds two values, returning None if the result overflows under
+    /// checked or big-integer arithmetic
+    pub fn add(self, op1: i64, op2: i64) -> Option<i64> {
+        match self {
+            Arithmetic::Checked | Arithmetic::BigInt => op1.checked_add(op2),
+            Arithmetic::Wrapping   => Some(op1.wrapping_add(op2)),
+            Arithmetic::Saturating => Some(op1.saturating_add(op2)),
+        }
+    }
+
+    /// multiplies two values, returning None if the result overflows under
+    /// checked or big-integer arithmetic
+    pub fn mul(self, op1: i64, op2: i64) -> Option<i64> {
+        match self {
+            Arithmetic::Checked | Arithmetic::BigInt => op1.checked_mul(op2),
+            Arithmetic::Wrapping   => Some(op1.wrapping_mul(op2)),
+            Arithmetic::Saturating => Some(op1.saturating_mul(op2)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+}
+
+impl ParameterMode {
+    fn decode(n: i64) -> Option<Self> {
+        match n {
+            0 => Some(ParameterMode::Position),
+            1 => Some(ParameterMode::Immediate),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParameterMode> for i64 {
+    fn from(mode: ParameterMode) -> Self {
+        match mode {
+            ParameterMode::Position  => 0,
+            ParameterMode::Immediate => 1,
+        }
+    }
+}
+
+struct Instr {
+    opcode: i64,
+    param_modes: [ParameterMode; 3],
+}
+
+impl Instr {
+    // returns None if any of the parameter modes are invalid
+    fn decode(n: i64) -> Option<Self> {
+        let opcode = n % 100;
+        let param_modes = [
+            ParameterMode::decode((n / 100)   % 10)?,
+            ParameterMode::decode((n / 1000)  % 10)?,
+            ParameterMode::decode((n / 10000) % 10)?,
+        ];
+
+        Some(Self { opcode, param_modes })
+    }
+}
+
+impl fmt::Debug for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f, "{}{}{}{}",
+            Into::<i64>::into(self.param_modes[2]),
+            Into::<i64>::into(self.param_modes[1]),
+            Into::<i64>::into(self.param_modes[0]),
+            self.opcode)
+    }
+}
+
+/// An Intcode program is a list of integers separated by commas. To run one,
+/// start by looking at the first integer (position 0). Here, you will find an
+/// opcode. The instruction pointer is then moved past the opcode and its
+/// parameters and execution continues.
+pub struct Intcode {
+    pub memory: Vec<i64>,
+    // memory as it was when the program was loaded
+    loaded: Vec<i64>,
+    instr_ptr: usize,
+
+    input:  VecDeque<i64>,
+    output: VecDeque<i64>,
+
+    arithmetic: Arithmetic,
+    // exact values of addresses too large for an i64, under big-integer
+    // arithmetic
+    wide: BTreeMap<usize, BigInt>,
+    opcodes: Arc<OpcodeTable>,
+    pub status: Status,
+
+    // instructions executed, and an undo log of them if time-travel is enabled
+    steps: u64,
+    history: Option<History>,
+    // inputs consumed and outputs produced, if recording is enabled
+    transcript: Option<Transcript>,
+    // every executed instruction, if tracing is enabled
+    trace: Option<Vec<Executed>>,
+
+    // protected ranges of memory, and the first read-only address written to
+    // by the current instruction
+    protected: Vec<(Range<usize>, Protection)>,
+    violation: Option<usize>,
+}
+
+impl Intcode {
+    /// parses the text of a program, panicking if it is malformed; see
+    /// `Intcode::parse_program`
+    pub fn parse(prog_text: String) -> Vec<i64> {
+        Self::parse_program(&prog_text).unwrap_or_else(|err| panic!("invalid program: {}", err))
+    }
+
+    pub fn new(memory: Vec<i64>) -> Self {
+        Self {
+            loaded: memory.clone(),
+            memory,
+            instr_ptr: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            arithmetic: Arithmetic::Checked,
+            wide: BTreeMap::new(),
+            opcodes: OpcodeTable::shared_standard(),
+            status: Status::Initial,
+            steps: 0,
+            history: None,
+            transcript: None,
+            trace: None,
+            protected: vec![],
+            violation: None,
+        }
+    }
+
+    /// sets the overflow policy for add and multiply, following the builder
+    /// pattern; defaults to checked arithmetic
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// defines a custom opcode for this program only, following the builder
+    /// pattern; the standard opcodes can also be replaced
+    pub fn with_opcode(mut self, code: i64, op: Opcode) -> Self {
+        Arc::make_mut(&mut self.opcodes).register(code, op);
+        self
+    }
+
+    /// the opcodes understood by this program
+    pub fn opcodes(&self) -> &OpcodeTable {
+        &self.opcodes
+    }
+
+    /// starts execution at an address other than 0, following the builder
+    /// pattern; used to resume a program stopped elsewhere
+    pub fn with_instr_ptr(mut self, instr_ptr: usize) -> Self {
+        self.instr_ptr = instr_ptr;
+        self
+    }
+
+    pub fn set_noun_verb(mut self, noun: i64, verb: i64) -> Self {
+        self.memory[1] = noun;
+        self.memory[2] = verb;
+        self
+    }
+
+    /// adds input to an intcode program
+    pub fn input(&mut self, input: i64) {
+        self.input.push_back(input);
+    }
+
+    /// adds input to an intcode program, following the builder pattern
+    pub fn with_input(mut self, input: i64) -> Self {
+        self.input.push_back(input);
+        self
+    }
+
+    /// pops an item from the output queue
+    pub fn output(&mut self) -> Option<i64> {
+        self.output.pop_front()
+    }
+
+    /// iterate through the output queue
+    pub fn output_iter(&self) -> impl Iterator<Item=&i64> {
+        self.output.iter()
+    }
+
+    fn get_param(&self, pn: usize, mode: ParameterMode) -> i64 {
+        let param = self.memory[self.instr_ptr + pn];
+        match mode {
+            ParameterMode::Position => self.memory[param as usize],
+            ParameterMode::Immediate => param,
+        }
+    }
+
+    /// the address of the next instruction to execute
+    pub fn instr_ptr(&self) -> usize {
+        self.instr_ptr
+    }
+
+    /// the overflow policy for arithmetic instructions
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// writes to memory; opcodes must write through this so that their
+    /// effects can be undone and protected memory is respected
+    pub fn set(&mut self, pos: usize, val: i64) {
+        if self.is_protected(pos, Protection::ReadOnly) {
+            // the instruction is faulted once it finishes executing
+            self.violation.get_or_insert(pos);
+            return;
+        }
+        if let Some(ref mut history) = self.history {
+            let wide = self.wide.get(&pos).cloned();
+            history.record(Change::Write { addr: pos, old: self.memory[pos], wide });
+        }
+        self.memory[pos] = val;
+        self.wide.remove(&pos);
+    }
+
+    /// takes the next value from the input queue, for use by opcodes
+    pub fn pop_input(&mut self) -> Option<i64> {
+        let input = self.input.pop_front();
+        if let Some(input) = input {
+            if let Some(ref mut history) = self.history {
+                history.record(Change::Input(input));
+            }
+            if let Some(ref mut transcript) = self.transcript {
+                transcript.push(Event::Input(input));
+            }
+        }
+        input
+    }
+
+    /// adds a value to the output queue, for use by opcodes
+    pub fn push_output(&mut self, output: i64) {
+        if let Some(ref mut history) = self.history {
+            history.record(Change::Output);
+        }
+        if let Some(ref mut transcript) = self.transcript {
+            transcript.push(Event::Output(output));
+        }
+        self.output.push_back(output);
+    }
+
+    fn decode_instr(&self) -> Option<Instr> {
+        Instr::decode(self.memory[self.instr_ptr])
+    }
+
+    // executes the instruction at the instruction pointer
+    fn exec_instr(&mut self) {
+        let word = self.memory[self.instr_ptr];
+        if self.is_protected(self.instr_ptr, Protection::NoExec) {
+            self.status = Status::Faulted(Fault::NotExecutable { instr_ptr: self.instr_ptr, instr: word });
+            return;
+        }
+
+        let instr = match self.decode_instr() {
+            Some(instr) => instr,
+            None => {
+                self.status = Status::Faulted(Fault::InvalidMode {
+                    instr_ptr: self.instr_ptr,
+                    instr: self.memory[self.instr_ptr],
+                });
+                return;
+            },
+        };
+
+        let op = match self.opcodes.get(instr.opcode) {
+            Some(&op) => op,
+            None => {
+                self.status = Status::Faulted(Fault::UnknownOpcode {
+                    instr_ptr: self.instr_ptr,
+                    opcode: instr.opcode,
+                });
+                return;
+            },
+        };
+
+        // a value too large for an i64 cannot be used as an address
+        let too_large = op.params.iter().enumerate().any(|(pn, &param)| {
+            let addr_param = param == Param::Write || instr.param_modes[pn] == ParameterMode::Position;
+            addr_param && self.wide.contains_key(&(self.instr_ptr + pn + 1))
+        });
+        if too_large {
+            self.status = Status::Faulted(Fault::TooLarge { instr_ptr: self.instr_ptr, instr: word });
+            return;
+        }
+
+        // read parameters are resolved to their values, while write
+        // parameters are always used as addresses
+        let mut args = [0; 3];
+        for (pn, param) in op.params.iter().enumerate() {
+            args[pn] = match param {
+                Param::Read  => self.get_param(pn + 1, instr.param_modes[pn]),
+                Param::Write => self.get_param(pn + 1, ParameterMode::Immediate),
+            };
+        }
+
+        // writes to read-only memory are caught before the instruction has
+        // any effect where possible
+        let protected = op.params.iter()
+            .zip(args.iter())
+            .find(|&(&param, &addr)| {
+                param == Param::Write && self.is_protected(addr as usize, Protection::ReadOnly)
+            });
+        if let Some((_, &addr)) = protected {
+            self.status = Status::Faulted(Fault::WriteProtected {
+                instr_ptr: self.instr_ptr,
+                instr: word,
+                addr: addr as usize,
+            });
+            return;
+        }
+
+        let flow = (op.exec)(self, &args[..op.arity()]);
+        let flow = match self.violation.take() {
+            Some(addr) => Flow::Fault(Fault::WriteProtected { instr_ptr: self.instr_ptr, instr: word, addr }),
+            None => flow,
+        };
+        match flow {
+            Flow::Next => self.instr_ptr += 1 + op.arity(),
+            Flow::Jump(addr) => self.instr_ptr = addr,
+            Flow::Wait => self.status = Status::Waiting,
+            Flow::Halt => self.status = Status::Halted,
+            Flow::Fault(fault) => self.status = Status::Faulted(fault),
+        }
+    }
+
+    /// executes a single instruction; has no effect once the program has
+    /// halted or faulted
+    pub fn step(&mut self) {
+        let prev_status = match self.status {
+            Status::Halted | Status::Faulted(_) => return,
+            ref status => status.clone(),
+        };
+
+        if let Some(ref mut history) = self.history {
+            history.begin(self.instr_ptr, prev_status);
+        }
+        let executing = Executed { addr: self.instr_ptr, instr: self.memory[self.instr_ptr] };
+
+        self.status = Status::Running;
+        self.exec_instr();
+
+        // blocking on input or faulting does not execute the instruction
+        let executed = matches!(self.status, Status::Running | Status::Halted);
+        if executed {
+            self.steps += 1;
+            if let Some(ref mut trace) = self.trace {
+                trace.push(executing);
+            }
+        }
+        if let Some(ref mut history) = self.history {
+            history.commit(executed, matches!(self.status, Status::Faulted(_)));
+        }
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            self.step();
+            if self.status != Status::Running {
+                break;
+            }
+        }
+    }
+
+    /// the number of instructions executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+/// Used to chain multiple Intcode programs together
+pub struct IntcodeChain {
+    programs: Vec<Intcode>,
+    feedback: bool,
+    schedule: Schedule,
+    round_limit: Option<usize>,
+    // values passed on by each stage, if recorded
+    taps: Option<Vec<Vec<i64>>>,
+}
+
+impl IntcodeChain {
+    /// sets the feedback loop
+    pub fn with_feedback(mut self) -> Self {
+        self.feedback = true;
+        self
+    }
+
+    /// input is provided to the first program in the chain
+    pub fn input(&mut self, input: i64) {
+        self.programs[0].input(input);
+    }
+
+    pub fn output(&mut self) -> Option<i64> {
+        let n_progs = self.programs.len();
+        self.programs[n_progs - 1].output()
+    }
+
+    /// Records every value each stage passes on to the next, following the
+    /// builder pattern; with feedback, this includes the final stage passing
+    /// values back to the first.
+    pub fn with_taps(mut self) -> Self {
+        self.taps = Some(vec![vec![]; self.programs.len()]);
+        self
+    }
+
+    /// a stage of the chain, giving access to its status and memory
+    pub fn stage(&self, n: usize) -> &Intcode {
+        &self.programs[n]
+    }
+
+    pub fn stages(&self) -> &[Intcode] {
+        &self.programs
+    }
+
+    /// the values passed on by a stage so far, in order, if taps are enabled
+    pub fn tap(&self, n: usize) -> Option<&[i64]> {
+        self.taps.as_ref().map(|taps| taps[n].as_slice())
+    }
+}
+
+impl<I> From<I> for IntcodeChain
+where I: Iterator<Item=Intcode>
+{
+    fn from(it: I) -> Self {
+        Self {
+            programs: it.collect(),
+            feedback: false,
+            schedule: Schedule::RoundRobin,
+            round_limit: None,
+            taps: None,
+        }
+    }
+}
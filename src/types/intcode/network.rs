@@ -0,0 +1,247 @@
+/*
+** src/types/intcode/network.rs
+** Intcode programs connected by an arbitrary graph
+*/
+
+use std::convert::TryFrom;
+use std::iter;
+use std::mem;
+
+use crate::types::intcode::threaded::run_threads;
+use crate::types::intcode::{Intcode, NetworkError};
+
+/// Where a program's output is sent
+#[derive(Clone, Debug, PartialEq)]
+pub enum Route {
+    /// every output value is sent to each of the programs; with no programs,
+    /// output stays in the program's output queue
+    To(Vec<usize>),
+    /// output is split into packets of `len` values, the first of which is
+    /// the address of the program the remaining values are sent to
+    Addressed { len: usize },
+}
+
+/// A packet addressed to a program outside of the network
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    pub addr: i64,
+    pub values: Vec<i64>,
+}
+
+// The output taken from a program, split along its route
+pub(super) struct Routed {
+    /// the values for each destination, in the order they were sent
+    pub deliveries: Vec<(usize, Vec<i64>)>,
+    /// packets addressed to programs outside of the `n_progs` programs
+    pub stray: Vec<Packet>,
+    /// every value taken from the program, including packet addresses
+    pub sent: Vec<i64>,
+}
+
+// Takes a program's output and splits it along its route, for each backend
+// to deliver in its own way. Output with nowhere to go stays with the
+// program, as does a partial packet until the rest of its values arrive.
+pub(super) fn route_output(prog: &mut Intcode, route: &Route, n_progs: usize) -> Routed {
+    let mut routed = Routed { deliveries: vec![], stray: vec![], sent: vec![] };
+    match route {
+        Route::To(targets) if targets.is_empty() => (),
+        Route::To(targets) => {
+            let values = iter::from_fn(|| prog.output()).collect::<Vec<_>>();
+            if !values.is_empty() {
+                routed.deliveries.extend(targets.iter().map(|&to| (to, values.clone())));
+                routed.sent = values;
+            }
+        },
+        Route::Addressed { len } => while prog.output_iter().count() >= *len {
+            let addr = prog.output().unwrap();
+            let values = (1..*len).map(|_| prog.output().unwrap()).collect::<Vec<_>>();
+            routed.sent.push(addr);
+            routed.sent.extend(values.iter());
+            match usize::try_from(addr) {
+                Ok(to) if to < n_progs => routed.deliveries.push((to, values)),
+                _ => routed.stray.push(Packet { addr, values }),
+            }
+        },
+    }
+    routed
+}
+
+/// Intcode programs, each running on its own thread, whose outputs are routed
+/// to each other's inputs. A program's address is the order it was added in.
+/// Output may be sent to several programs (fan-out), several programs may
+/// send to the same program (fan-in), and programs may address packets to
+/// each other. Values sent together are received together, so packets from
+/// different programs are never interleaved.
+pub struct Network {
+    programs: Vec<Intcode>,
+    routes: Vec<Route>,
+    stray: Vec<Packet>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self {
+            programs: vec![],
+            routes: vec![],
+            stray: vec![],
+        }
+    }
+
+    /// Adds a program, following the builder pattern. Its output is not sent
+    /// anywhere until it is connected.
+    pub fn with_program(mut self, prog: Intcode) -> Self {
+        self.programs.push(prog);
+        self.routes.push(Route::To(vec![]));
+        self
+    }
+
+    /// Sends the output of one program to another, following the builder
+    /// pattern. Replaces addressing on the source program.
+    pub fn with_edge(mut self, from: usize, to: usize) -> Self {
+        assert!(from < self.programs.len(), "no program at address {}", from);
+        assert!(to < self.programs.len(), "no program at address {}", to);
+        match self.routes[from] {
+            Route::To(ref mut targets) => targets.push(to),
+            _ => self.routes[from] = Route::To(vec![to]),
+        }
+        self
+    }
+
+    /// Splits the output of a program into addressed packets of `len` values,
+    /// including the address, following the builder pattern. Replaces any
+    /// edges from the program.
+    pub fn with_addressing(mut self, node: usize, len: usize) -> Self {
+        assert!(node < self.programs.len(), "no program at address {}", node);
+        assert!(len > 0, "packets must contain an address");
+        self.routes[node] = Route::Addressed { len };
+        self
+    }
+
+    /// the route taken by the output of each program
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn program(&self, node: usize) -> &Intcode {
+        &self.programs[node]
+    }
+
+    pub fn input(&mut self, node: usize, input: i64) {
+        self.programs[node].input(input);
+    }
+
+    /// output which was not routed to another program
+    pub fn output(&mut self, node: usize) -> Option<i64> {
+        self.programs[node].output()
+    }
+
+    /// packets addressed to programs outside of the network, in the order
+    /// they were sent by each program
+    pub fn stray_packets(&mut self) -> Vec<Packet> {
+        mem::take(&mut self.stray)
+    }
+
+    /// Runs every program on its own thread until each has halted. If a
+    /// program faults, or every program still running is waiting with nothing
+    /// in flight, the remaining programs are shut down and an error returned.
+    /// Values a program never received are left in its input, so the network
+    /// can be resumed after providing more input.
+    pub fn run(&mut self) -> Result<(), NetworkError> {
+        let programs = mem::take(&mut self.programs);
+        let finished = run_threads(programs, self.routes.clone(), false);
+        let err = finished.error();
+
+        self.programs = finished.programs;
+        for (prog, values) in self.programs.iter_mut().zip(finished.undelivered) {
+            for val in values {
+                prog.input(val);
+            }
+        }
+        self.stray.extend(finished.stray);
+
+        err.map_or(Ok(()), Err)
+    }
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::Status;
+
+    // outputs its first input, then halts
+    const ECHO_ONCE: &[i64] = &[3, 0, 4, 0, 99];
+
+    // outputs each input, forever
+    const ECHO: &[i64] = &[3, 7, 4, 7, 1105, 1, 0, 0];
+
+    fn network(programs: &[&[i64]]) -> Network {
+        programs.iter().fold(Network::new(), |network, memory| {
+            network.with_program(Intcode::new(memory.to_vec()))
+        })
+    }
+
+    #[test]
+    fn fan_out() {
+        let mut network = network(&[ECHO_ONCE, ECHO_ONCE, ECHO_ONCE]).with_edge(0, 1).with_edge(0, 2);
+        assert_eq!(network.routes()[0], Route::To(vec![1, 2]));
+        network.input(0, 7);
+        assert_eq!(network.run(), Ok(()));
+        assert_eq!(network.output(0), None);
+        assert_eq!(network.output(1), Some(7));
+        assert_eq!(network.output(2), Some(7));
+    }
+
+    #[test]
+    fn fan_in() {
+        // the third program outputs the sum of its two inputs
+        let adder: &[i64] = &[3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+        let mut network = network(&[&[104, 1, 99], &[104, 2, 99], adder]).with_edge(0, 2).with_edge(1, 2);
+        assert_eq!(network.run(), Ok(()));
+        assert_eq!(network.output(2), Some(3));
+        assert_eq!(network.program(2).status, Status::Halted);
+    }
+
+    #[test]
+    fn addressed_packets() {
+        // sends 5 to 1, 6 to 9 which does not exist and 8 to 2, then starts
+        // a packet it never finishes
+        let sender: &[i64] = &[104, 1, 104, 5, 104, 9, 104, 6, 104, 2, 104, 8, 104, 1, 99];
+        let mut network = network(&[sender, ECHO_ONCE, ECHO_ONCE]).with_edge(0, 1).with_addressing(0, 2);
+        assert_eq!(network.routes()[0], Route::Addressed { len: 2 });
+        assert_eq!(network.run(), Ok(()));
+        assert_eq!(network.output(1), Some(5));
+        assert_eq!(network.output(2), Some(8));
+        assert_eq!(network.stray_packets(), vec![Packet { addr: 9, values: vec![6] }]);
+        assert_eq!(network.stray_packets(), vec![]);
+        assert_eq!(network.output(0), Some(1));
+    }
+
+    #[test]
+    fn runs_to_idle() {
+        let mut network = network(&[ECHO, ECHO_ONCE]).with_edge(0, 1);
+        assert_eq!(network.run(), Err(NetworkError::Deadlock { waiting: vec![0, 1] }));
+
+        // more input resumes the network
+        network.input(0, 3);
+        assert_eq!(network.run(), Err(NetworkError::Deadlock { waiting: vec![0] }));
+        assert_eq!(network.output(1), Some(3));
+        assert_eq!(network.program(1).status, Status::Halted);
+
+        // values sent to a program after it halts are left in its input
+        network.input(0, 4);
+        assert_eq!(network.run(), Err(NetworkError::Deadlock { waiting: vec![0] }));
+        assert_eq!(network.program(1).input.iter().cloned().collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no program at address 3")]
+    fn rejects_missing_source() {
+        network(&[ECHO, ECHO]).with_edge(3, 0);
+    }
+}
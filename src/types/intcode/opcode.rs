@@ -0,0 +1,352 @@
+/*
+** src/types/intcode/opcode.rs
+*/
+
+use std::cmp::Ordering;
+use std::sync::{Arc, OnceLock};
+
+use crate::types::intcode::{Arithmetic, Fault, Intcode};
+
+/// How an instruction uses one of its parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    Read,   // a value, resolved according to the parameter mode
+    Write,  // an address which the instruction writes to
+}
+
+/// What the VM does once an instruction has executed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Flow {
+    Next,          // move past the instruction and its parameters
+    Jump(usize),   // move the instruction pointer to an address
+    Wait,          // block on input without moving the instruction pointer
+    Halt,          // halt the program
+    Fault(Fault),  // stop the program with a fault
+}
+
+/// The definition of an opcode. Read parameters are passed to `exec` as their
+/// resolved values and write parameters as the addresses to write to; `exec`
+/// performs any side-effects through the VM and returns the resulting control
+/// flow.
+#[derive(Clone, Copy)]
+pub struct Opcode {
+    pub name: &'static str,
+    pub params: &'static [Param],
+    pub exec: fn(&mut Intcode, &[i64]) -> Flow,
+}
+
+impl Opcode {
+    /// the number of parameters taken by the opcode
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+}
+
+/// Maps each opcode (0 to 99) to its definition
+#[derive(Clone)]
+pub struct OpcodeTable {
+    ops: Vec<Option<Opcode>>,
+}
+
+impl OpcodeTable {
+    /// a table with no opcodes defined
+    pub fn empty() -> Self {
+        Self { ops: vec![None; 100] }
+    }
+
+    /// the table of opcodes defined by the day 2 and day 5 puzzles
+    pub fn standard() -> Self {
+        let mut table = Self::empty();
+        for &(code, op) in STANDARD.iter() {
+            table.register(code, op);
+        }
+        table
+    }
+
+    // a shared copy of the standard table, so that new programs do not need
+    // to build their own
+    pub(super) fn shared_standard() -> Arc<Self> {
+        static SHARED: OnceLock<Arc<OpcodeTable>> = OnceLock::new();
+        SHARED.get_or_init(|| Arc::new(Self::standard())).clone()
+    }
+
+    /// Defines an opcode, returning the definition it replaced. Instructions
+    /// hold only two decimal digits for the opcode and three for parameter
+    /// modes, so the opcode must be in 0..100 with at most 3 parameters.
+    pub fn register(&mut self, code: i64, op: Opcode) -> Option<Opcode> {
+        assert!((0..100).contains(&code), "opcode {} is out of range", code);
+        assert!(op.arity() <= 3, "opcode {} takes more than 3 parameters", code);
+        self.ops[code as usize].replace(op)
+    }
+
+    pub fn get(&self, code: i64) -> Option<&Opcode> {
+        if (0..100).contains(&code) {
+            self.ops[code as usize].as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// iterate through the defined opcodes and their definitions
+    pub fn iter(&self) -> impl Iterator<Item=(i64, &Opcode)> {
+        self.ops.iter()
+            .enumerate()
+            .filter_map(|(code, op)| op.as_ref().map(|op| (code as i64, op)))
+    }
+}
+
+const RRW: &[Param] = &[Param::Read, Param::Read, Param::Write];
+const RR: &[Param] = &[Param::Read, Param::Read];
+
+const STANDARD: [(i64, Opcode); 9] = [
+    (1,  Opcode { name: "add", params: RRW, exec: add }),
+    (2,  Opcode { name: "mul", params: RRW, exec: mul }),
+    (3,  Opcode { name: "in",  params: &[Param::Write], exec: input }),
+    (4,  Opcode { name: "out", params: &[Param::Read], exec: output }),
+    (5,  Opcode { name: "jt",  params: RR, exec: jump_if_true }),
+    (6,  Opcode { name: "jf",  params: RR, exec: jump_if_false }),
+    (7,  Opcode { name: "lt",  params: RRW, exec: less_than }),
+    (8,  Opcode { name: "eq",  params: RRW, exec: equal }),
+    (99, Opcode { name: "halt", params: &[], exec: halt }),
+];
+
+// stores the result of an arithmetic instruction, or faults the program if
+// the result overflowed; under big-integer arithmetic, a result which
+// overflows or has an operand too large for an i64 is computed exactly
+fn arith(vm: &mut Intcode, opcode: i64, args: &[i64], res: Option<i64>) -> Flow {
+    let exact = vm.arithmetic == Arithmetic::BigInt
+        && (res.is_none() || vm.wide_operand(0).is_some() || vm.wide_operand(1).is_some());
+    if exact {
+        let (op1, op2) = (vm.exact_operand(0), vm.exact_operand(1));
+        let res = if opcode == 1 { &op1 + &op2 } else { &op1 * &op2 };
+        vm.set_exact(args[2] as usize, res);
+        return Flow::Next;
+    }
+
+    match res {
+        Some(res) => {
+            vm.set(args[2] as usize, res);
+            Flow::Next
+        },
+        None => Flow::Fault(Fault::Overflow {
+            instr_ptr: vm.instr_ptr,
+            opcode,
+            op1: args[0],
+            op2: args[1],
+        }),
+    }
+}
+
+// opcode 1: add
+// 3 parameters
+// adds the values specified by parameters 1 and 2 and stores the result at
+// the address specified by parameter 3
+fn add(vm: &mut Intcode, args: &[i64]) -> Flow {
+    let res = vm.arithmetic.add(args[0], args[1]);
+    arith(vm, 1, args, res)
+}
+
+// opcode 2: multiply
+// 3 parameters
+// multiplies the values specified by parameters 1 and 2 and stores the result
+// at the address specified by parameter 3
+fn mul(vm: &mut Intcode, args: &[i64]) -> Flow {
+    let res = vm.arithmetic.mul(args[0], args[1]);
+    arith(vm, 2, args, res)
+}
+
+// opcode 3: input
+// 1 parameter
+// takes a single input and saves it to the address specified by parameter 1
+fn input(vm: &mut Intcode, args: &[i64]) -> Flow {
+    if let Some(input) = vm.pop_input() {
+        vm.set(args[0] as usize, input);
+        Flow::Next
+    } else {
+        // if there is no input, transition to waiting
+        Flow::Wait
+    }
+}
+
+// faults the program if a read parameter is too large for an i64, where it
+// must be used as one
+fn too_large(vm: &Intcode, pn: usize) -> Option<Flow> {
+    vm.wide_operand(pn).map(|_| Flow::Fault(Fault::TooLarge {
+        instr_ptr: vm.instr_ptr,
+        instr: vm.memory[vm.instr_ptr],
+    }))
+}
+
+// whether a read parameter is non-zero
+fn non_zero(vm: &Intcode, args: &[i64], pn: usize) -> bool {
+    args[pn] != 0 || vm.wide_operand(pn).is_some()
+}
+
+// compares two read parameters, exactly if either is too large for an i64
+fn compare(vm: &Intcode, args: &[i64]) -> Ordering {
+    match (vm.wide_operand(0), vm.wide_operand(1)) {
+        (None, None) => args[0].cmp(&args[1]),
+        _ => vm.exact_operand(0).cmp(&vm.exact_operand(1)),
+    }
+}
+
+// opcode 4: output
+// 1 parameter
+// outputs the value specified by parameter 1
+fn output(vm: &mut Intcode, args: &[i64]) -> Flow {
+    if let Some(fault) = too_large(vm, 0) {
+        return fault;
+    }
+    vm.push_output(args[0]);
+    Flow::Next
+}
+
+// opcode 5: jump-if-true
+// 2 parameters
+// if parameter 1 is non-zero, sets the instruction pointer to the value from
+// parameter 2; otherwise, does nothing
+fn jump_if_true(vm: &mut Intcode, args: &[i64]) -> Flow {
+    if non_zero(vm, args, 0) {
+        too_large(vm, 1).unwrap_or(Flow::Jump(args[1] as usize))
+    } else {
+        Flow::Next
+    }
+}
+
+// opcode 6: jump-if-false
+// 2 parameters
+// if parameter 1 is zero, sets the instruction pointer to the value from
+// parameter 2; otherwise, does nothing
+fn jump_if_false(vm: &mut Intcode, args: &[i64]) -> Flow {
+    if !non_zero(vm, args, 0) {
+        too_large(vm, 1).unwrap_or(Flow::Jump(args[1] as usize))
+    } else {
+        Flow::Next
+    }
+}
+
+// opcode 7: less-than
+// 3 parameters
+// if parameter 1 is less than parameter 2, store 1 in the position given by
+// the third parameter; otherwise, store 0
+fn less_than(vm: &mut Intcode, args: &[i64]) -> Flow {
+    let res = compare(vm, args) == Ordering::Less;
+    vm.set(args[2] as usize, res as i64);
+    Flow::Next
+}
+
+// opcode 8: equal
+// 3 parameters
+// if parameter 1 is equal to parameter 2, store 1 in the position given by
+// the third parameter; otherwise, store 0
+fn equal(vm: &mut Intcode, args: &[i64]) -> Flow {
+    let res = compare(vm, args) == Ordering::Equal;
+    vm.set(args[2] as usize, res as i64);
+    Flow::Next
+}
+
+// opcode 99: no parameters
+// the program is finished and should immediately halt
+fn halt(_: &mut Intcode, _: &[i64]) -> Flow {
+    Flow::Halt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::analysis::{DecodeError, Disassembly};
+    use crate::types::intcode::{validate, Diagnostic, Problem, Status};
+
+    // opcode 9: double
+    // 2 parameters
+    // stores twice the value specified by parameter 1 at the address
+    // specified by parameter 2
+    fn double(vm: &mut Intcode, args: &[i64]) -> Flow {
+        vm.set(args[1] as usize, args[0] * 2);
+        Flow::Next
+    }
+
+    // a replacement add which subtracts instead
+    fn sub(vm: &mut Intcode, args: &[i64]) -> Flow {
+        vm.set(args[2] as usize, args[0] - args[1]);
+        Flow::Next
+    }
+
+    const DOUBLE: Opcode = Opcode { name: "dbl", params: &[Param::Read, Param::Write], exec: double };
+    const SUB: Opcode = Opcode { name: "sub", params: RRW, exec: sub };
+
+    // doubles 21 into 8, then outputs it
+    const DOUBLES: &[i64] = &[109, 21, 8, 4, 8, 99, 0, 0, 0];
+
+    // adds 5 and 3 into 7, then outputs it
+    const ADDS: &[i64] = &[1101, 5, 3, 7, 4, 7, 99, 0];
+
+    fn run(mut prog: Intcode) -> (Status, Option<i64>) {
+        prog.run();
+        let out = prog.output();
+        (prog.status, out)
+    }
+
+    #[test]
+    fn registers_opcodes() {
+        let mut table = OpcodeTable::standard();
+        assert_eq!(table.iter().count(), 9);
+        assert!(table.get(9).is_none() && table.get(100).is_none() && table.get(-1).is_none());
+        assert!(table.register(9, DOUBLE).is_none());
+        assert_eq!(table.register(1, SUB).map(|op| op.name), Some("add"));
+        assert_eq!(table.iter().map(|(code, op)| (code, op.name)).take(2).collect::<Vec<_>>(),
+            vec![(1, "sub"), (2, "mul")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "opcode 100 is out of range")]
+    fn rejects_out_of_range_opcodes() {
+        OpcodeTable::empty().register(100, DOUBLE);
+    }
+
+    #[test]
+    fn runs_custom_opcodes() {
+        let prog = Intcode::new(DOUBLES.to_vec()).with_opcode(9, DOUBLE);
+        assert_eq!(run(prog), (Status::Halted, Some(42)));
+
+        let prog = Intcode::new(DOUBLES.to_vec());
+        assert_eq!(run(prog), (Status::Faulted(Fault::UnknownOpcode { instr_ptr: 0, opcode: 9 }), None));
+    }
+
+    #[test]
+    fn overrides_standard_opcodes() {
+        assert_eq!(run(Intcode::new(ADDS.to_vec())), (Status::Halted, Some(8)));
+        assert_eq!(run(Intcode::new(ADDS.to_vec()).with_opcode(1, SUB)), (Status::Halted, Some(2)));
+    }
+
+    #[test]
+    fn copies_table_on_write() {
+        // programs share the standard table until one of them changes it
+        let prog = Intcode::new(ADDS.to_vec());
+        let shared = prog.opcodes.clone();
+        assert!(Arc::ptr_eq(&shared, &Intcode::new(ADDS.to_vec()).opcodes));
+
+        let custom = prog.with_opcode(1, SUB);
+        assert!(!Arc::ptr_eq(&shared, &custom.opcodes));
+        assert_eq!(custom.opcodes().get(1).map(|op| op.name), Some("sub"));
+        assert_eq!(shared.get(1).map(|op| op.name), Some("add"));
+        assert!(Arc::ptr_eq(&shared, &Intcode::new(ADDS.to_vec()).opcodes));
+        assert_eq!(run(Intcode::new(ADDS.to_vec())), (Status::Halted, Some(8)));
+    }
+
+    #[test]
+    fn analysis_uses_custom_params() {
+        let prog = Intcode::new(DOUBLES.to_vec()).with_opcode(9, DOUBLE);
+        let disasm = Disassembly::new(DOUBLES, prog.opcodes());
+        assert_eq!(disasm.to_string(), "    0  dbl 21, [8]\n    3  out [8]\n    5  halt\n");
+        assert_eq!(prog.validate(), vec![]);
+        assert_eq!(validate(DOUBLES, &OpcodeTable::standard()), vec![Diagnostic {
+            addr: 0,
+            problem: Problem::Undecodable(DecodeError::UnknownOpcode(9)),
+        }]);
+
+        // the second parameter is written to, so may not be immediate
+        let prog = Intcode::new(vec![1109, 21, 3, 99]).with_opcode(9, DOUBLE);
+        assert_eq!(prog.validate(), vec![Diagnostic { addr: 0, problem: Problem::ImmediateWrite { param: 1 } }]);
+    }
+}
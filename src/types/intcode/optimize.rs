@@ -0,0 +1,244 @@
+/*
+** src/types/intcode/optimize.rs
+** peephole optimization of Intcode memory images
+*/
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::types::intcode::analysis::{DecodeError, Disassembly, Instruction, Operand};
+use crate::types::intcode::{Divergence, Intcode, OpcodeTable, Param, ParameterMode, Transcript};
+
+/// Reasons a program could not be optimized
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptimizeError {
+    /// reachable code could not be decoded
+    Decode { addr: usize, err: DecodeError },
+    /// an instruction overwrites an instruction which may run later
+    SelfModifying { instr: usize, target: usize },
+    /// the original program does not reproduce one of the transcripts, so it
+    /// cannot be used for verification
+    Unreproducible { transcript: usize, divergence: Divergence },
+    /// the optimized program diverged from one of the transcripts
+    Diverged { transcript: usize, divergence: Divergence },
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptimizeError::Decode { addr, err } => write!(f, "{}: {}", addr, err),
+            OptimizeError::SelfModifying { instr, target } => write!(
+                f, "{}: instruction writes into code at {}", instr, target),
+            OptimizeError::Unreproducible { transcript, divergence } => write!(
+                f, "transcript {} does not match the original program: {}", transcript, divergence),
+            OptimizeError::Diverged { transcript, divergence } => write!(
+                f, "optimized program diverged from transcript {}: {}", transcript, divergence),
+        }
+    }
+}
+
+/// An optimized memory image, along with what was changed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Optimized {
+    pub memory: Vec<i64>,
+    /// instructions on constants replaced by a move of their result
+    pub folded: usize,
+    /// moves rewritten into the canonical `add x, 0` form
+    pub moves: usize,
+    /// cells of unreachable code cleared to 0
+    pub removed: usize,
+}
+
+// encodes an instruction word from an opcode and its parameter modes
+fn encode(opcode: i64, modes: [ParameterMode; 2]) -> i64 {
+    opcode + 100 * i64::from(modes[0]) + 1000 * i64::from(modes[1])
+}
+
+// The instruction rewritten as a move into its destination, if it is an
+// arithmetic or comparison instruction which has a constant result or simply
+// copies one of its operands. Returns the new cells and whether the result
+// was constant.
+fn rewrite(memory: &[i64], instr: &Instruction) -> Option<([i64; 4], bool)> {
+    let (a, b) = (instr.operands.first()?, instr.operands.get(1)?);
+    let dst = instr.operands.get(2)?.value;
+    let imm = |val| [encode(1, [ParameterMode::Immediate, ParameterMode::Immediate]), val, 0, dst];
+    let copy = |op: &Operand| [encode(1, [op.mode, ParameterMode::Immediate]), op.value, 0, dst];
+
+    let rewritten = match (instr.opcode, a.immediate(), b.immediate()) {
+        // results which overflow are left to fault at runtime
+        (1, Some(x), Some(y)) => (imm(x.checked_add(y)?), true),
+        (2, Some(x), Some(y)) => (imm(x.checked_mul(y)?), true),
+        (7, Some(x), Some(y)) => (imm((x < y) as i64), true),
+        (8, Some(x), Some(y)) => (imm((x == y) as i64), true),
+        (2, Some(0), _) | (2, _, Some(0)) => (imm(0), true),
+        (1, Some(0), _) => (copy(b), false),
+        (1, _, Some(0)) => (copy(a), false),
+        (2, Some(1), _) => (copy(b), false),
+        (2, _, Some(1)) => (copy(a), false),
+        _ => return None,
+    };
+
+    // already in canonical form
+    if rewritten.0[..] == memory[instr.cells()] { None } else { Some(rewritten) }
+}
+
+// checks that the original program reproduces each transcript and that the
+// optimized program still does
+fn verify(original: &[i64], optimized: &[i64], transcripts: &[Transcript]) -> Result<(), OptimizeError> {
+    for (n, transcript) in transcripts.iter().enumerate() {
+        transcript.replay(&mut Intcode::new(original.to_vec()))
+            .map_err(|divergence| OptimizeError::Unreproducible { transcript: n, divergence })?;
+        transcript.replay(&mut Intcode::new(optimized.to_vec()))
+            .map_err(|divergence| OptimizeError::Diverged { transcript: n, divergence })?;
+    }
+    Ok(())
+}
+
+/// Applies peephole optimizations to a program without moving any of its
+/// code, so that every address stays valid:
+///
+/// * add, multiply and comparison instructions on immediate operands are
+///   folded into a move of their result
+/// * moves written as `add 0, x`, `mul x, 1` and so on are rewritten into the
+///   canonical `add x, 0`
+/// * unreachable code following unconditional jumps and halts is cleared
+///
+/// Only instructions whose cells are never read or written as data are
+/// rewritten, and programs which access memory or jump through computed
+/// addresses are left unchanged, since any cell could be affected. The result
+/// is then verified by replaying each of the recorded transcripts against
+/// both the original and the optimized program.
+pub fn optimize(memory: &[i64], transcripts: &[Transcript]) -> Result<Optimized, OptimizeError> {
+    let disasm = Disassembly::new(memory, &OpcodeTable::standard());
+    if let Some(&(instr, target)) = disasm.live_opcode_writes().first() {
+        return Err(OptimizeError::SelfModifying { instr, target });
+    }
+    if let Some((&addr, &err)) = disasm.errors.iter().next() {
+        return Err(OptimizeError::Decode { addr, err });
+    }
+
+    let mut opt = Optimized { memory: memory.to_vec(), folded: 0, moves: 0, removed: 0 };
+
+    let dynamic_reads = disasm.instrs.values()
+        .flat_map(|instr| instr.operands.iter())
+        .any(|op| op.patched && op.kind == Param::Read && op.mode == ParameterMode::Position);
+    if dynamic_reads || !disasm.dynamic_writes().is_empty() || !disasm.dynamic_jumps.is_empty() {
+        verify(memory, &opt.memory, transcripts)?;
+        return Ok(opt);
+    }
+
+    // cells which are read or written as data
+    let mut data = disasm.static_writes().map(|(_, target)| target).collect::<BTreeSet<_>>();
+    for instr in disasm.instrs.values() {
+        let reads = instr.operands.iter().filter_map(|op| op.read_addr());
+        data.extend(reads.filter(|&addr| addr >= 0).map(|addr| addr as usize));
+    }
+
+    for instr in disasm.instrs.values() {
+        if instr.cells().any(|cell| data.contains(&cell)) {
+            continue;
+        }
+        if let Some((cells, constant)) = rewrite(memory, instr) {
+            opt.memory[instr.cells()].copy_from_slice(&cells);
+            if constant {
+                opt.folded += 1;
+            } else {
+                opt.moves += 1;
+            }
+        }
+    }
+
+    // clear the cells between each unconditional jump or halt and the next
+    // reachable instruction, skipping any cells used as data
+    for instr in disasm.instrs.values().filter(|instr| !instr.falls_through()) {
+        let next = disasm.instrs.range(instr.next_addr()..)
+            .next()
+            .map_or(memory.len(), |(&addr, _)| addr);
+        for cell in instr.next_addr()..next {
+            if !data.contains(&cell) && opt.memory[cell] != 0 {
+                opt.memory[cell] = 0;
+                opt.removed += 1;
+            }
+        }
+    }
+
+    verify(memory, &opt.memory, transcripts)?;
+    Ok(opt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::{Event, Observed};
+
+    // adds two constants, then copies the sum with a multiply by 1
+    const ARITH: &[i64] = &[1101, 2, 3, 11, 1002, 11, 1, 12, 4, 12, 99, 0, 0];
+
+    // jumps over an instruction which never runs
+    const SKIPS: &[i64] = &[1105, 1, 7, 1101, 7, 7, 0, 104, 6, 99];
+
+    fn record(memory: &[i64], input: &[i64]) -> Transcript {
+        let mut prog = Intcode::new(memory.to_vec()).with_recording();
+        for &val in input {
+            prog.input(val);
+        }
+        prog.run();
+        prog.take_transcript().unwrap()
+    }
+
+    #[test]
+    fn folds_and_rewrites_moves() {
+        let opt = optimize(ARITH, &[record(ARITH, &[])]).unwrap();
+        assert_eq!(opt, Optimized {
+            memory: vec![1101, 5, 0, 11, 1001, 11, 0, 12, 4, 12, 99, 0, 0],
+            folded: 1,
+            moves: 1,
+            removed: 0,
+        });
+
+        // canonical moves are left alone
+        assert_eq!(optimize(&opt.memory, &[]).unwrap().memory, opt.memory);
+    }
+
+    #[test]
+    fn clears_unreachable_code() {
+        let opt = optimize(SKIPS, &[record(SKIPS, &[])]).unwrap();
+        assert_eq!(opt.memory, vec![1105, 1, 7, 0, 0, 0, 0, 104, 6, 99]);
+        assert_eq!(opt.removed, 3);
+    }
+
+    #[test]
+    fn leaves_data_and_overflow() {
+        // the output reads the second operand of the add as data
+        let reads_code = [1101, 2, 3, 7, 4, 2, 99, 0];
+        assert_eq!(optimize(&reads_code, &[]).unwrap().folded, 0);
+
+        // an overflowing multiply must still fault when it runs
+        let overflows = [1102, i64::MAX, 2, 5, 99, 0];
+        assert_eq!(optimize(&overflows, &[]).unwrap().memory, overflows);
+    }
+
+    #[test]
+    fn verifies_transcripts() {
+        let other = record(SKIPS, &[]);
+        let err = optimize(ARITH, &[record(ARITH, &[]), other]).unwrap_err();
+        assert_eq!(err, OptimizeError::Unreproducible {
+            transcript: 1,
+            divergence: Divergence {
+                index: 0,
+                expected: Some(Event::Output(6)),
+                actual: Observed::Output(5),
+            },
+        });
+    }
+
+    #[test]
+    fn refuses_unanalyzable_programs() {
+        assert_eq!(optimize(&[1101, 0, 99, 4, 1, 0, 0, 0], &[]).unwrap_err(),
+            OptimizeError::SelfModifying { instr: 0, target: 4 });
+        assert_eq!(optimize(&[3, 11, 1, 11, 6, 6, 1100, 1, 2, 0, 99, 0], &[]).unwrap_err(),
+            OptimizeError::SelfModifying { instr: 2, target: 6 });
+        assert_eq!(optimize(&[42], &[]).unwrap_err(),
+            OptimizeError::Decode { addr: 0, err: DecodeError::UnknownOpcode(42) });
+    }
+}
@@ -0,0 +1,184 @@
+/*
+** src/types/intcode/phase.rs
+** searching for the amplifier phase settings which produce the best signal
+*/
+
+use std::thread;
+
+use crate::types::intcode::{ChainOutcome, Intcode, IntcodeChain};
+use crate::utils::Permutations;
+
+/// A phase setting sequence and the signal it produced
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhaseSetting {
+    pub phases: Vec<i64>,
+    pub signal: i64,
+}
+
+/// Searches every ordering of a set of phase settings for a chain of
+/// amplifiers, one per phase, running the same program. Orderings are split
+/// between threads.
+pub struct PhaseSearch<'a> {
+    program: &'a [i64],
+    input: i64,
+    feedback: bool,
+    threads: usize,
+}
+
+impl<'a> PhaseSearch<'a> {
+    pub fn new(program: &'a [i64]) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self { program, input: 0, feedback: false, threads }
+    }
+
+    /// connects the amplifiers in a feedback loop, following the builder
+    /// pattern
+    pub fn with_feedback(mut self) -> Self {
+        self.feedback = true;
+        self
+    }
+
+    /// sets the input to the first amplifier, 0 by default, following the
+    /// builder pattern
+    pub fn with_input(mut self, input: i64) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// sets the number of threads to search with, following the builder
+    /// pattern; defaults to the available parallelism
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The signal produced by a phase setting sequence, if every amplifier
+    /// halted and the last produced a signal; with no phases there are no
+    /// amplifiers to produce one
+    pub fn signal(&self, phases: &[i64]) -> Option<i64> {
+        if phases.is_empty() {
+            return None;
+        }
+        let mut chain = IntcodeChain::from(phases.iter()
+            .map(|&phase| Intcode::new(self.program.to_vec()).with_input(phase)));
+        if self.feedback {
+            chain = chain.with_feedback();
+        }
+
+        chain.input(self.input);
+        match chain.run().outcome {
+            ChainOutcome::Halted => chain.output(),
+            _ => None,
+        }
+    }
+
+    /// Tries every ordering of the phase settings, returning the one which
+    /// produces the highest signal; ties go to the ordering generated first.
+    /// Orderings which produce no signal are ignored.
+    pub fn run(&self, phase_settings: &[i64]) -> Option<PhaseSetting> {
+        let candidates = Permutations::new(phase_settings)
+            .map(|phases| phases.into_iter().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let chunk_size = candidates.len().div_ceil(self.threads);
+
+        // chunks are joined in order, so signals line up with the candidates
+        let signals = thread::scope(|scope| {
+            let handles = candidates.chunks(chunk_size.max(1))
+                .map(|chunk| scope.spawn(move || {
+                    chunk.iter().map(|phases| self.signal(phases)).collect::<Vec<_>>()
+                }))
+                .collect::<Vec<_>>();
+            handles.into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let best = signals.into_iter()
+            .enumerate()
+            .filter_map(|(pos, signal)| signal.map(|signal| (signal, pos)))
+            .fold(None, |best: Option<(i64, usize)>, (signal, pos)| match best {
+                Some((s, _)) if s >= signal => best,
+                _ => Some((signal, pos)),
+            });
+        best.map(|(signal, pos)| PhaseSetting { phases: candidates[pos].clone(), signal })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::compile;
+
+    // each amplifier appends its phase to the signal as a decimal digit
+    const APPEND: &str = "
+        input p;
+        input s;
+        output s * 10 + p;
+    ";
+
+    // every ordering produces the same signal
+    const SUM: &str = "
+        input p;
+        input s;
+        output s + p;
+    ";
+
+    // as APPEND, but the first amplifier overflows with phase 2 and waits
+    // for input forever with phase 1, given an input of 1
+    const PICKY: &str = "
+        input p;
+        input s;
+        if s == 1 && p == 2 {
+            x = 9223372036854775807 + s;
+        }
+        if s == 1 && p == 1 {
+            input x;
+        }
+        output s * 10 + p;
+    ";
+
+    fn search(program: &[i64], phases: &[i64], threads: usize) -> Option<PhaseSetting> {
+        PhaseSearch::new(program).with_threads(threads).run(phases)
+    }
+
+    #[test]
+    fn finds_best_ordering() {
+        let program = compile(APPEND).unwrap();
+        let best = PhaseSetting { phases: vec![2, 1, 0], signal: 210 };
+        for threads in 1..=7 {
+            assert_eq!(search(&program, &[0, 1, 2], threads), Some(best.clone()), "{} threads", threads);
+        }
+
+        let search = PhaseSearch::new(&program).with_input(9);
+        assert_eq!(search.signal(&[1, 2]), Some(912));
+        assert_eq!(search.run(&[4]), Some(PhaseSetting { phases: vec![4], signal: 94 }));
+    }
+
+    #[test]
+    fn ties_go_to_first_ordering() {
+        let program = compile(SUM).unwrap();
+        let phases = [3, 1, 4, 2];
+        let first = Permutations::new(&phases).next().unwrap().into_iter().cloned().collect::<Vec<_>>();
+        for &threads in [1, 2, 5, 24, 100].iter() {
+            let best = search(&program, &phases, threads).unwrap();
+            assert_eq!((best.phases.as_slice(), best.signal), (first.as_slice(), 10), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn ignores_failed_orderings() {
+        let program = compile(PICKY).unwrap();
+        let search = PhaseSearch::new(&program).with_input(1).with_threads(2);
+        assert_eq!(search.signal(&[2, 0, 1]), None);
+        assert_eq!(search.signal(&[1, 0, 2]), None);
+        assert_eq!(search.run(&[0, 1, 2]), Some(PhaseSetting { phases: vec![0, 2, 1], signal: 1021 }));
+        assert_eq!(search.run(&[1, 2]), None);
+    }
+
+    #[test]
+    fn empty_phase_set() {
+        let program = compile(APPEND).unwrap();
+        assert_eq!(search(&program, &[], 1), None);
+        assert_eq!(search(&program, &[], 4), None);
+    }
+}
@@ -0,0 +1,39 @@
+/*
+** src/types/intcode/poll.rs
+*/
+
+use crate::types::intcode::{Fault, Intcode, Status};
+
+/// Why a polled program stopped running
+#[derive(Clone, Debug, PartialEq)]
+pub enum Yield {
+    /// the program is waiting for input
+    NeedInput,
+    /// the program produced a value
+    Output(i64),
+    Halted,
+    Faulted(Fault),
+}
+
+impl Intcode {
+    /// Runs the program until it needs input, produces output, halts or
+    /// faults, so that its driver can decide what to do next. Output already
+    /// waiting in the output queue is yielded first, one value per call.
+    pub fn poll(&mut self) -> Yield {
+        loop {
+            if let Some(out) = self.output() {
+                return Yield::Output(out);
+            }
+            match self.status {
+                Status::Halted => return Yield::Halted,
+                Status::Faulted(ref fault) => return Yield::Faulted(fault.clone()),
+                _ => (),
+            }
+
+            self.step();
+            if self.status == Status::Waiting {
+                return Yield::NeedInput;
+            }
+        }
+    }
+}
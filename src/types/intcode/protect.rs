@@ -0,0 +1,148 @@
+/*
+** src/types/intcode/protect.rs
+*/
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use crate::types::intcode::analysis::Disassembly;
+use crate::types::intcode::Intcode;
+
+/// A restriction on how a range of memory may be used; violating it faults
+/// the program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protection {
+    ReadOnly,  // the range may not be written to
+    NoExec,    // instructions may not be executed from the range
+}
+
+// groups a sorted sequence of addresses into contiguous ranges
+fn ranges(addrs: impl Iterator<Item=usize>) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for addr in addrs {
+        match ranges.last_mut() {
+            Some(range) if range.end == addr => range.end += 1,
+            _ => ranges.push(addr..addr + 1),
+        }
+    }
+    ranges
+}
+
+impl Intcode {
+    /// Protects a range of memory, following the builder pattern. Writing to a
+    /// read-only address faults the program with `Fault::WriteProtected`
+    /// before the instruction has any effect, and executing an instruction at
+    /// a non-executable address faults it with `Fault::NotExecutable`.
+    pub fn with_protection(mut self, range: Range<usize>, protection: Protection) -> Self {
+        self.protected.push((range, protection));
+        self
+    }
+
+    /// Protects the program according to a static disassembly of its current
+    /// memory, following the builder pattern: instructions become read-only,
+    /// apart from operands which the analysis found to be overwritten, and
+    /// every other address, except words which are written before they run,
+    /// becomes non-executable. A fault then means the program modified or
+    /// strayed outside the code the analysis found.
+    pub fn with_code_protection(self) -> Self {
+        let disasm = Disassembly::explore(&self.memory, &self.opcodes, |d| d.errors.is_empty());
+        let exec = |cell: &usize| disasm.instrs.contains_key(cell) || disasm.rewritten.contains_key(cell);
+
+        let code = disasm.instrs.values()
+            .flat_map(|instr| instr.cells())
+            .collect::<BTreeSet<_>>();
+        let read_only = ranges(code.iter().cloned().filter(|cell| !disasm.patched.contains(cell)));
+        let no_exec = ranges((0..self.memory.len()).filter(|cell| !exec(cell)));
+
+        let mut prog = self;
+        for range in read_only {
+            prog = prog.with_protection(range, Protection::ReadOnly);
+        }
+        for range in no_exec {
+            prog = prog.with_protection(range, Protection::NoExec);
+        }
+        prog
+    }
+
+    /// the protected ranges of memory
+    pub fn protection(&self) -> &[(Range<usize>, Protection)] {
+        &self.protected
+    }
+
+    // whether or not an address has the given protection
+    pub(super) fn is_protected(&self, addr: usize, protection: Protection) -> bool {
+        self.protected.iter().any(|(range, p)| *p == protection && range.contains(&addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::{Fault, Status};
+    use crate::utils::PuzzleInput;
+
+    #[test]
+    fn read_only() {
+        let mut prog = Intcode::new(vec![1101, 1, 1, 5, 99, 0])
+            .with_protection(5..6, Protection::ReadOnly);
+        prog.run();
+        assert_eq!(prog.status, Status::Faulted(Fault::WriteProtected { instr_ptr: 0, instr: 1101, addr: 5 }));
+        assert_eq!(prog.memory[5], 0);
+
+        // an input is not consumed by an instruction which faults
+        let mut prog = Intcode::new(vec![3, 3, 99, 0])
+            .with_protection(3..4, Protection::ReadOnly)
+            .with_input(7);
+        prog.run();
+        assert_eq!(prog.status, Status::Faulted(Fault::WriteProtected { instr_ptr: 0, instr: 3, addr: 3 }));
+        assert_eq!(prog.memory[3], 0);
+        assert_eq!(prog.pop_input(), Some(7));
+    }
+
+    #[test]
+    fn no_exec() {
+        let mut prog = Intcode::new(vec![1105, 1, 4, 99, 99])
+            .with_protection(4..5, Protection::NoExec);
+        prog.run();
+        assert_eq!(prog.status, Status::Faulted(Fault::NotExecutable { instr_ptr: 4, instr: 99 }));
+
+        // the range is only checked when an instruction runs from it
+        let mut prog = Intcode::new(vec![1105, 1, 4, 99, 99])
+            .with_protection(3..4, Protection::NoExec);
+        prog.run();
+        assert_eq!(prog.status, Status::Halted);
+    }
+
+    #[test]
+    fn code_protection() {
+        let prog = Intcode::new(vec![1101, 2, 3, 7, 4, 7, 99, 0]).with_code_protection();
+        assert_eq!(prog.protection(), &[
+            (0..7, Protection::ReadOnly),
+            (1..4, Protection::NoExec),
+            (5..6, Protection::NoExec),
+            (7..8, Protection::NoExec),
+        ]);
+
+        // the operand at 7 is written, so it is left writable, but the
+        // computed write into the code at 12 faults
+        let mut prog = Intcode::new(vec![1101, 0, 12, 7, 1101, 1, 98, 0, 1105, 1, 12, 0, 104, 5, 99])
+            .with_code_protection();
+        prog.run();
+        assert_eq!(prog.status, Status::Faulted(Fault::WriteProtected { instr_ptr: 4, instr: 1101, addr: 12 }));
+    }
+
+    #[test]
+    fn protects_day_5() {
+        let memory = Intcode::parse(PuzzleInput::new(5).next().unwrap());
+
+        // the opcode at 6 is written before it runs, so it may be executed
+        let mut prog = Intcode::new(memory.clone()).with_code_protection().with_input(1);
+        prog.run();
+        assert_eq!(prog.status, Status::Halted);
+
+        // the second part overwrites the first instruction once it has run
+        let mut prog = Intcode::new(memory).with_code_protection().with_input(5);
+        prog.run();
+        assert_eq!(prog.status, Status::Faulted(Fault::WriteProtected { instr_ptr: 284, instr: 1101, addr: 0 }));
+    }
+}
@@ -0,0 +1,213 @@
+/*
+** src/types/intcode/schedule.rs
+** iterative scheduling of the programs in an IntcodeChain
+*/
+
+use std::mem;
+
+use crate::types::intcode::{IntcodeChain, Route, Scheduler, SchedulerOutcome, Status};
+
+/// The order and length of the turns programs in a chain are given. Every
+/// round gives each program one turn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+    /// programs take turns in chain order, each running until it halts or
+    /// waits for input
+    RoundRobin,
+    /// programs take turns from the end of the chain to the start, so each
+    /// consumes its input before its predecessor produces more
+    Reverse,
+    /// programs take turns in chain order, each executing at most the given
+    /// number of instructions
+    TimeSlice(u64),
+}
+
+impl Schedule {
+    // the order programs take their turns in each round
+    pub(super) fn order(self, n_progs: usize) -> Box<dyn Iterator<Item=usize>> {
+        match self {
+            Schedule::Reverse => Box::new((0..n_progs).rev()),
+            Schedule::RoundRobin | Schedule::TimeSlice(_) => Box::new(0..n_progs),
+        }
+    }
+}
+
+/// How a run of a chain ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChainOutcome {
+    /// every program halted
+    Halted,
+    /// the given program faulted
+    Faulted(usize),
+    /// a full round passed without any program executing an instruction
+    Deadlocked,
+    /// the round limit was reached first
+    RoundLimit,
+}
+
+/// The state of a chain once it stops running
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainReport {
+    pub outcome: ChainOutcome,
+    /// the number of rounds run
+    pub rounds: usize,
+    pub halted: Vec<usize>,
+    /// programs which have neither halted nor faulted, including any cut off
+    /// by the round limit or a time slice
+    pub waiting: Vec<usize>,
+}
+
+impl ChainReport {
+    /// whether or not the chain deadlocked
+    pub fn deadlocked(&self) -> bool {
+        self.outcome == ChainOutcome::Deadlocked
+    }
+}
+
+impl IntcodeChain {
+    /// sets the scheduling policy, following the builder pattern; the default
+    /// is `Schedule::RoundRobin`
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// limits the number of rounds a run may take, following the builder
+    /// pattern
+    pub fn with_round_limit(mut self, limit: usize) -> Self {
+        self.round_limit = Some(limit);
+        self
+    }
+
+    // each program's output goes to the next, and the final program's to the
+    // first with feedback
+    pub(super) fn routes(&self) -> Vec<Route> {
+        let n_progs = self.programs.len();
+        (0..n_progs)
+            .map(|n| match n + 1 < n_progs {
+                true => Route::To(vec![n + 1]),
+                false if self.feedback => Route::To(vec![0]),
+                false => Route::To(vec![]),
+            })
+            .collect()
+    }
+
+    /// Runs the programs on a `Scheduler` in rounds according to the schedule
+    /// until every program has halted, one faults, every program left is
+    /// waiting for input or the round limit is reached. Each program's output
+    /// is passed to the next; with feedback, the final program's output is
+    /// passed to the first until it halts. The signal is left in the final
+    /// program's output, along with any values fed back to the first program
+    /// which it did not read before halting; values not consumed by other
+    /// programs are left in their input.
+    pub fn run(&mut self) -> ChainReport {
+        let n_progs = self.programs.len();
+        let mut sched = Scheduler::new().with_schedule(self.schedule);
+        if let Some(limit) = self.round_limit {
+            sched = sched.with_round_limit(limit);
+        }
+        if self.taps.is_some() {
+            sched = sched.with_taps();
+        }
+        let routes = self.routes();
+        for (prog, route) in mem::take(&mut self.programs).into_iter().zip(routes) {
+            let pid = sched.spawn(prog);
+            sched.route(pid, route);
+        }
+
+        let outcome = match sched.run() {
+            SchedulerOutcome::Halted => ChainOutcome::Halted,
+            SchedulerOutcome::Faulted(n) => ChainOutcome::Faulted(n),
+            SchedulerOutcome::Idle => ChainOutcome::Deadlocked,
+            SchedulerOutcome::RoundLimit => ChainOutcome::RoundLimit,
+        };
+        let rounds = sched.rounds();
+        if let Some(ref mut taps) = self.taps {
+            for (n, tap) in taps.iter_mut().enumerate() {
+                tap.extend(sched.tap(n).unwrap_or_default());
+            }
+        }
+        let undelivered = (0..n_progs).map(|n| sched.undelivered(n)).collect::<Vec<_>>();
+        self.programs = sched.into_processes();
+
+        // values fed back to the first program after it halted are the
+        // chain's output; any others remain as input
+        for (n, values) in undelivered.into_iter().enumerate() {
+            for val in values {
+                match self.feedback && n == 0 && self.programs[0].status == Status::Halted {
+                    true => self.programs[n_progs - 1].output.push_back(val),
+                    false => self.programs[n].input(val),
+                }
+            }
+        }
+
+        let (halted, waiting) = (0..n_progs)
+            .filter(|&n| !matches!(self.programs[n].status, Status::Faulted(_)))
+            .partition(|&n| self.programs[n].status == Status::Halted);
+        ChainReport { outcome, rounds, halted, waiting }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::Intcode;
+    use crate::utils::PuzzleInput;
+
+    fn amplifiers(program: &[i64], phases: &[i64], schedule: Schedule) -> IntcodeChain {
+        let mut chain = IntcodeChain::from(phases.iter()
+            .map(|&phase| Intcode::new(program.to_vec()).with_input(phase)))
+            .with_feedback()
+            .with_schedule(schedule);
+        chain.input(0);
+        chain
+    }
+
+    #[test]
+    fn time_slices() {
+        // a slice may end just before the first program reads the signal
+        // fed back to it, or just before it halts
+        let program = Intcode::parse(PuzzleInput::new(7).next().unwrap());
+        for slice in 1..=10 {
+            let mut chain = amplifiers(&program, &[9, 8, 7, 6, 5], Schedule::TimeSlice(slice));
+            let report = chain.run();
+            assert_eq!(report.outcome, ChainOutcome::Halted, "slice {}", slice);
+            assert_eq!(chain.output(), Some(33036630), "slice {}", slice);
+        }
+    }
+
+    #[test]
+    fn reports() {
+        let program = Intcode::parse(PuzzleInput::new(7).next().unwrap());
+        let mut chain = amplifiers(&program, &[9, 8, 7, 6, 5], Schedule::RoundRobin).with_round_limit(2);
+        assert_eq!(chain.run(), ChainReport {
+            outcome: ChainOutcome::RoundLimit,
+            rounds: 2,
+            halted: vec![],
+            waiting: vec![0, 1, 2, 3, 4],
+        });
+
+        // every program waits on the one before it, with no input to start
+        let mut chain = IntcodeChain::from((0..3).map(|_| Intcode::new(vec![3, 5, 4, 5, 99, 0])))
+            .with_feedback();
+        let report = chain.run();
+        assert!(report.deadlocked());
+        assert_eq!(report.waiting, vec![0, 1, 2]);
+
+        let programs = vec![Intcode::new(vec![3, 5, 4, 5, 99, 0]), Intcode::new(vec![42])];
+        let mut chain = IntcodeChain::from(programs.into_iter());
+        chain.input(1);
+        assert_eq!(chain.run().outcome, ChainOutcome::Faulted(1));
+
+        // the first program faults after the signal is fed back to it, which
+        // is left unread in its input rather than taken as the chain's output
+        let programs = vec![Intcode::new(vec![3, 5, 4, 5, 42, 0]), Intcode::new(vec![3, 5, 4, 5, 99, 0])];
+        let mut chain = IntcodeChain::from(programs.into_iter()).with_feedback();
+        chain.input(1);
+        let report = chain.run();
+        assert_eq!(report.outcome, ChainOutcome::Faulted(0));
+        assert_eq!(report.halted, vec![1]);
+        assert_eq!(chain.output(), None);
+        assert_eq!(chain.stage(0).input.iter().cloned().collect::<Vec<_>>(), vec![1]);
+    }
+}
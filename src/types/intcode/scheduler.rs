@@ -0,0 +1,351 @@
+/*
+** src/types/intcode/scheduler.rs
+** time-sliced multitasking of Intcode programs on a single thread
+*/
+
+use std::mem;
+
+use crate::types::intcode::network::route_output;
+use crate::types::intcode::{Fault, Intcode, Packet, Route, Schedule, Status};
+
+/// identifies a process within a scheduler
+pub type Pid = usize;
+
+/// The scheduling state of a process
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessState {
+    /// the process can run
+    Ready,
+    /// the process is waiting for input which has not been sent
+    Blocked,
+    Halted,
+    Faulted(Fault),
+}
+
+/// Why a scheduler stopped running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchedulerOutcome {
+    /// every process halted
+    Halted,
+    /// the given process faulted
+    Faulted(Pid),
+    /// every process which has not halted is blocked on input
+    Idle,
+    /// the round limit was reached first
+    RoundLimit,
+}
+
+/// Runs many programs on a single thread, giving each ready process a turn
+/// according to its schedule, by default a slice of 1000 instructions. A
+/// process's output is delivered along its route to the input of other
+/// processes after each of its turns; as with `Network`, output may go to
+/// several processes or be split into addressed packets, and packets
+/// addressed outside the scheduler are kept aside. Values which a process
+/// will never read, because they were sent after it stopped or were left in
+/// its input when it stopped, are kept as undelivered.
+pub struct Scheduler {
+    procs: Vec<Intcode>,
+    routes: Vec<Route>,
+    schedule: Schedule,
+    round_limit: Option<usize>,
+    rounds: usize,
+    stray: Vec<Packet>,
+    undelivered: Vec<Vec<i64>>,
+    // output routed by each process, if recorded
+    taps: Option<Vec<Vec<i64>>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            procs: vec![],
+            routes: vec![],
+            schedule: Schedule::TimeSlice(1000),
+            round_limit: None,
+            rounds: 0,
+            stray: vec![],
+            undelivered: vec![],
+            taps: None,
+        }
+    }
+
+    /// sets the number of instructions in a time slice, 1000 by default,
+    /// following the builder pattern
+    pub fn with_slice(mut self, slice: u64) -> Self {
+        self.schedule = Schedule::TimeSlice(slice.max(1));
+        self
+    }
+
+    /// sets the order and length of each process's turns, following the
+    /// builder pattern
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = match schedule {
+            Schedule::TimeSlice(slice) => Schedule::TimeSlice(slice.max(1)),
+            schedule => schedule,
+        };
+        self
+    }
+
+    /// Records every value each process routes elsewhere, following the
+    /// builder pattern. Output which stays with a process is not recorded.
+    pub fn with_taps(mut self) -> Self {
+        self.taps = Some(vec![vec![]; self.procs.len()]);
+        self
+    }
+
+    /// limits the number of rounds each call to `run` may take, following the
+    /// builder pattern
+    pub fn with_round_limit(mut self, limit: usize) -> Self {
+        self.round_limit = Some(limit);
+        self
+    }
+
+    /// Adds a process, returning its ID. Its output stays in its output queue
+    /// until it is routed elsewhere.
+    pub fn spawn(&mut self, prog: Intcode) -> Pid {
+        self.procs.push(prog);
+        self.routes.push(Route::To(vec![]));
+        self.undelivered.push(vec![]);
+        if let Some(ref mut taps) = self.taps {
+            taps.push(vec![]);
+        }
+        self.procs.len() - 1
+    }
+
+    /// sets where a process's output is delivered
+    pub fn route(&mut self, pid: Pid, route: Route) {
+        self.routes[pid] = route;
+    }
+
+    /// sends a value to a process from outside the scheduler
+    pub fn send(&mut self, pid: Pid, val: i64) {
+        self.deliver(pid, Some(val));
+    }
+
+    // gives values to a process, or keeps them aside if it has stopped
+    fn deliver<I: IntoIterator<Item=i64>>(&mut self, pid: Pid, values: I) {
+        match self.procs[pid].status {
+            Status::Halted | Status::Faulted(_) => self.undelivered[pid].extend(values),
+            _ => for val in values {
+                self.procs[pid].input(val);
+            },
+        }
+    }
+
+    /// takes the values sent to a process which it will never read
+    pub fn undelivered(&mut self, pid: Pid) -> Vec<i64> {
+        mem::take(&mut self.undelivered[pid])
+    }
+
+    /// the values routed by a process so far, in order, if taps are enabled
+    pub fn tap(&self, pid: Pid) -> Option<&[i64]> {
+        self.taps.as_ref().map(|taps| taps[pid].as_slice())
+    }
+
+    /// output of a process which was not delivered elsewhere
+    pub fn output(&mut self, pid: Pid) -> Option<i64> {
+        self.procs[pid].output()
+    }
+
+    pub fn process(&self, pid: Pid) -> &Intcode {
+        &self.procs[pid]
+    }
+
+    pub fn state(&self, pid: Pid) -> ProcessState {
+        let prog = &self.procs[pid];
+        match prog.status {
+            Status::Halted => ProcessState::Halted,
+            Status::Faulted(ref fault) => ProcessState::Faulted(fault.clone()),
+            Status::Waiting if prog.input.is_empty() => ProcessState::Blocked,
+            _ => ProcessState::Ready,
+        }
+    }
+
+    /// the total number of rounds run so far
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// packets addressed to processes outside of the scheduler
+    pub fn stray_packets(&mut self) -> Vec<Packet> {
+        mem::take(&mut self.stray)
+    }
+
+    // hands back the processes, in order of ID
+    pub(super) fn into_processes(self) -> Vec<Intcode> {
+        self.procs
+    }
+
+    // Gives a process a turn, then delivers its output along its route. A
+    // process which stops leaves any input it did not read as undelivered.
+    fn turn(&mut self, pid: Pid) {
+        let prog = &mut self.procs[pid];
+        match self.schedule {
+            Schedule::TimeSlice(slice) => for _ in 0..slice {
+                prog.step();
+                if prog.status != Status::Running {
+                    break;
+                }
+            },
+            Schedule::RoundRobin | Schedule::Reverse => prog.run(),
+        }
+        if let Status::Halted | Status::Faulted(_) = prog.status {
+            self.undelivered[pid].extend(prog.input.drain(..));
+        }
+
+        let n_procs = self.procs.len();
+        let routed = route_output(&mut self.procs[pid], &self.routes[pid], n_procs);
+        if let Some(ref mut taps) = self.taps {
+            taps[pid].extend(routed.sent);
+        }
+        for (to, values) in routed.deliveries {
+            self.deliver(to, values);
+        }
+        self.stray.extend(routed.stray);
+    }
+
+    /// Gives every ready process a turn, in the order of its schedule, until every
+    /// process has halted, one faults, every process left is blocked on
+    /// input or the round limit is reached. Sending more input to an idle
+    /// scheduler allows it to be run again.
+    pub fn run(&mut self) -> SchedulerOutcome {
+        let mut rounds = 0;
+        loop {
+            let states = (0..self.procs.len()).map(|pid| self.state(pid)).collect::<Vec<_>>();
+            if let Some(pid) = states.iter().position(|s| matches!(s, ProcessState::Faulted(_))) {
+                return SchedulerOutcome::Faulted(pid);
+            }
+            if states.iter().all(|s| *s == ProcessState::Halted) {
+                return SchedulerOutcome::Halted;
+            }
+            if !states.contains(&ProcessState::Ready) {
+                return SchedulerOutcome::Idle;
+            }
+            if self.round_limit == Some(rounds) {
+                return SchedulerOutcome::RoundLimit;
+            }
+
+            // a process may become ready during the round from the output of
+            // a process before it
+            for pid in self.schedule.order(self.procs.len()) {
+                if self.state(pid) == ProcessState::Ready {
+                    self.turn(pid);
+                }
+            }
+            rounds += 1;
+            self.rounds += 1;
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::PuzzleInput;
+
+    // outputs each input, forever
+    const ECHO: &[i64] = &[3, 7, 4, 7, 1105, 1, 0, 0];
+
+    // outputs its first input, then halts
+    const ECHO_ONCE: &[i64] = &[3, 0, 4, 0, 99];
+
+    fn spawn(sched: &mut Scheduler, memory: &[i64]) -> Pid {
+        sched.spawn(Intcode::new(memory.to_vec()))
+    }
+
+    #[test]
+    fn feedback_loop() {
+        // day 7: the final amplifier's signal is fed back to the first after
+        // it halts, so is left undelivered
+        let program = Intcode::parse(PuzzleInput::new(7).next().unwrap());
+        let schedules = [Schedule::RoundRobin, Schedule::Reverse, Schedule::TimeSlice(1), Schedule::TimeSlice(7)];
+        for &schedule in schedules.iter() {
+            let mut sched = Scheduler::new().with_schedule(schedule);
+            for (n, &phase) in [9, 8, 7, 6, 5].iter().enumerate() {
+                let pid = sched.spawn(Intcode::new(program.clone()).with_input(phase));
+                sched.route(pid, Route::To(vec![(n + 1) % 5]));
+            }
+            sched.send(0, 0);
+            assert_eq!(sched.run(), SchedulerOutcome::Halted, "{:?}", schedule);
+            assert_eq!(sched.output(4), None, "{:?}", schedule);
+            assert_eq!(sched.undelivered(0), vec![33036630], "{:?}", schedule);
+            assert_eq!(sched.undelivered(0), vec![], "{:?}", schedule);
+        }
+    }
+
+    #[test]
+    fn fan_out() {
+        let mut sched = Scheduler::new();
+        let pids = (0..3).map(|_| spawn(&mut sched, ECHO_ONCE)).collect::<Vec<_>>();
+        sched.route(pids[0], Route::To(vec![pids[1], pids[2]]));
+        sched.send(pids[0], 7);
+        assert_eq!(sched.run(), SchedulerOutcome::Halted);
+        assert_eq!(sched.output(pids[0]), None);
+        assert_eq!(sched.output(pids[1]), Some(7));
+        assert_eq!(sched.output(pids[2]), Some(7));
+    }
+
+    #[test]
+    fn addressed_packets() {
+        // sends 5 to process 1, and 6 to process 9 which does not exist
+        let mut sched = Scheduler::new().with_taps();
+        let sender = spawn(&mut sched, &[104, 1, 104, 5, 104, 9, 104, 6, 99]);
+        let receiver = spawn(&mut sched, ECHO_ONCE);
+        sched.route(sender, Route::Addressed { len: 2 });
+        assert_eq!(sched.run(), SchedulerOutcome::Halted);
+        assert_eq!(sched.output(receiver), Some(5));
+        assert_eq!(sched.stray_packets(), vec![Packet { addr: 9, values: vec![6] }]);
+        assert_eq!(sched.tap(sender), Some(&[1, 5, 9, 6][..]));
+        assert_eq!(sched.tap(receiver), Some(&[][..]));
+    }
+
+    #[test]
+    fn idle_and_resumed() {
+        let mut sched = Scheduler::new();
+        let pid = spawn(&mut sched, ECHO);
+        assert_eq!(sched.run(), SchedulerOutcome::Idle);
+        assert_eq!(sched.state(pid), ProcessState::Blocked);
+
+        sched.send(pid, 3);
+        assert_eq!(sched.state(pid), ProcessState::Ready);
+        assert_eq!(sched.run(), SchedulerOutcome::Idle);
+        assert_eq!(sched.output(pid), Some(3));
+    }
+
+    #[test]
+    fn stopped_processes() {
+        let mut sched = Scheduler::new();
+        let faults = spawn(&mut sched, &[42]);
+        assert_eq!(sched.run(), SchedulerOutcome::Faulted(faults));
+        assert!(matches!(sched.state(faults), ProcessState::Faulted(_)));
+
+        // input left unread when a process halts, or sent after it halted,
+        // is never delivered
+        let mut sched = Scheduler::new();
+        let halts = spawn(&mut sched, &[99]);
+        sched.send(halts, 4);
+        assert_eq!(sched.run(), SchedulerOutcome::Halted);
+        sched.send(halts, 5);
+        assert_eq!(sched.undelivered(halts), vec![4, 5]);
+        assert!(sched.process(halts).input.is_empty());
+    }
+
+    #[test]
+    fn round_limit() {
+        // echoes its own output back to itself, forever
+        let mut sched = Scheduler::new().with_round_limit(3).with_taps();
+        let pid = spawn(&mut sched, ECHO);
+        sched.route(pid, Route::To(vec![pid]));
+        sched.send(pid, 1);
+        assert_eq!(sched.run(), SchedulerOutcome::RoundLimit);
+        assert_eq!(sched.rounds(), 3);
+        assert_eq!(sched.tap(pid), Some(&[1, 1, 1][..]));
+        assert_eq!(sched.state(pid), ProcessState::Ready);
+    }
+}
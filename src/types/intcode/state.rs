@@ -0,0 +1,379 @@
+/*
+** src/types/intcode/state.rs
+*/
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, prelude::*, BufReader};
+
+use crate::types::intcode::{Arithmetic, BigInt, Intcode, Protection, Status};
+
+// first line of every state file, followed by the format version
+const MAGIC: &str = "intcode-state";
+const VERSION: u32 = 1;
+
+/// Errors produced when saving or loading a persisted Intcode state
+#[derive(Debug)]
+pub enum StateError {
+    /// the underlying reader or writer failed
+    Io(io::Error),
+    /// the file does not start with the state file header
+    NotAStateFile,
+    /// the file was written by an unsupported version of the format
+    Version(u32),
+    /// a line of the file could not be parsed
+    Malformed { line: usize, reason: String },
+    /// the contents of the file do not match its checksum
+    Checksum { expected: u64, actual: u64 },
+    /// the file parsed but describes a state the VM could not be in
+    Invalid(String),
+    /// faulted programs cannot be resumed and so are not saved
+    Faulted,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Io(err) => write!(f, "{}", err),
+            StateError::NotAStateFile => write!(f, "not an Intcode state file"),
+            StateError::Version(v) => write!(f, "unsupported state file version {}", v),
+            StateError::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+            StateError::Checksum { expected, actual } => write!(
+                f, "checksum mismatch: expected {:016x}, found {:016x}", expected, actual),
+            StateError::Invalid(reason) => write!(f, "invalid state: {}", reason),
+            StateError::Faulted => write!(f, "cannot save a faulted program"),
+        }
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(err: io::Error) -> Self {
+        StateError::Io(err)
+    }
+}
+
+// 64-bit FNV-1a, used to detect corrupted state files
+fn fnv1a(data: &[u8], mut hash: u64) -> u64 {
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn join<T: ToString>(vals: impl Iterator<Item=T>) -> String {
+    vals.map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn status_name(status: &Status) -> Result<&'static str, StateError> {
+    match status {
+        Status::Initial    => Ok("initial"),
+        Status::Running    => Ok("running"),
+        Status::Waiting    => Ok("waiting"),
+        Status::Halted     => Ok("halted"),
+        Status::Faulted(_) => Err(StateError::Faulted),
+    }
+}
+
+fn arithmetic_name(arithmetic: Arithmetic) -> &'static str {
+    match arithmetic {
+        Arithmetic::Checked    => "checked",
+        Arithmetic::Wrapping   => "wrapping",
+        Arithmetic::Saturating => "saturating",
+        Arithmetic::BigInt     => "bigint",
+    }
+}
+
+fn protection_name(protection: Protection) -> &'static str {
+    match protection {
+        Protection::ReadOnly => "read-only",
+        Protection::NoExec   => "no-exec",
+    }
+}
+
+// the body of a state file is a fixed sequence of key-value lines
+const KEYS: [&str; 8] = [
+    "status", "arithmetic", "instr_ptr", "memory", "input", "output", "loaded", "protection",
+];
+
+impl Intcode {
+    /// Saves the complete state of a paused program so that it can be resumed
+    /// later with `Intcode::load_state`, including its memory as it was
+    /// loaded and its protected ranges. The state is written as a versioned
+    /// text file terminated by a checksum of its contents. Custom opcodes,
+    /// history, traces and transcripts are not saved.
+    pub fn save_state<W: Write>(&self, mut w: W) -> Result<(), StateError> {
+        let values = [
+            status_name(&self.status)?.to_owned(),
+            arithmetic_name(self.arithmetic).to_owned(),
+            self.instr_ptr.to_string(),
+            join((0..self.memory.len()).map(|addr| self.exact(addr))),
+            join(self.input.iter()),
+            join(self.output.iter()),
+            join(self.loaded.iter()),
+            join(self.protected.iter().map(|(range, protection)| {
+                format!("{}..{}:{}", range.start, range.end, protection_name(*protection))
+            })),
+        ];
+
+        let mut body = format!("{} {}\n", MAGIC, VERSION);
+        for (key, val) in KEYS.iter().zip(values.iter()) {
+            body.push_str(&format!("{} {}\n", key, val));
+        }
+
+        let checksum = fnv1a(body.as_bytes(), FNV_OFFSET);
+        w.write_all(body.as_bytes())?;
+        writeln!(w, "checksum {:016x}", checksum)?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// Loads a program state previously written by `Intcode::save_state`.
+    /// Files with a bad header, unknown version, corrupted contents or a state
+    /// which is inconsistent with its memory are rejected.
+    pub fn load_state<R: Read>(r: R) -> Result<Self, StateError> {
+        let mut lines = vec![];
+        for line in BufReader::new(r).lines() {
+            lines.push(line?);
+        }
+
+        // validate the header before anything else
+        let header = lines.first().ok_or(StateError::NotAStateFile)?;
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version.parse::<u32>()
+                .map_err(|_| StateError::NotAStateFile)?,
+            _ => return Err(StateError::NotAStateFile),
+        };
+        if version != VERSION {
+            return Err(StateError::Version(version));
+        }
+
+        if lines.len() != KEYS.len() + 2 {
+            return Err(StateError::Malformed {
+                line: lines.len(),
+                reason: format!("expected {} lines", KEYS.len() + 2),
+            });
+        }
+
+        // verify the checksum over the header and body lines
+        let checksum_line = &lines[KEYS.len() + 1];
+        let expected = match checksum_line.split_once(' ') {
+            Some(("checksum", hex)) => u64::from_str_radix(hex, 16).ok(),
+            _ => None,
+        }.ok_or_else(|| StateError::Malformed {
+            line: KEYS.len() + 2,
+            reason: "expected checksum".to_owned(),
+        })?;
+
+        let actual = lines[..=KEYS.len()].iter()
+            .fold(FNV_OFFSET, |hash, line| fnv1a(b"\n", fnv1a(line.as_bytes(), hash)));
+        if expected != actual {
+            return Err(StateError::Checksum { expected, actual });
+        }
+
+        // split each body line into its value, in the order they were written
+        let mut values = vec![];
+        for (i, (key, line)) in KEYS.iter().zip(lines[1..].iter()).enumerate() {
+            match line.split_once(' ') {
+                Some((k, val)) if k == *key => values.push(val),
+                _ => return Err(StateError::Malformed {
+                    line: i + 2,
+                    reason: format!("expected {}", key),
+                }),
+            }
+        }
+
+        let malformed = |line: usize, reason: &str| StateError::Malformed {
+            line: line + 2,
+            reason: reason.to_owned(),
+        };
+
+        let status = match values[0] {
+            "initial" => Status::Initial,
+            "running" => Status::Running,
+            "waiting" => Status::Waiting,
+            "halted"  => Status::Halted,
+            _ => return Err(malformed(0, "unknown status")),
+        };
+        let arithmetic = match values[1] {
+            "checked"    => Arithmetic::Checked,
+            "wrapping"   => Arithmetic::Wrapping,
+            "saturating" => Arithmetic::Saturating,
+            "bigint"     => Arithmetic::BigInt,
+            _ => return Err(malformed(1, "unknown arithmetic mode")),
+        };
+        let instr_ptr = values[2].parse::<usize>()
+            .map_err(|_| malformed(2, "invalid instruction pointer"))?;
+
+        let parse_list = |i: usize| -> Result<VecDeque<i64>, StateError> {
+            if values[i].is_empty() {
+                return Ok(VecDeque::new());
+            }
+            values[i].split(',')
+                .map(|s| s.parse::<i64>().map_err(|_| malformed(i, "invalid integer")))
+                .collect()
+        };
+        // values too large for an i64 are only kept under big-integer
+        // arithmetic
+        let mut wide = BTreeMap::new();
+        let mut memory = vec![];
+        let cells = values[3].split(',').filter(|_| !values[3].is_empty());
+        for (addr, s) in cells.enumerate() {
+            let val = match (s.parse::<i64>(), arithmetic) {
+                (Ok(val), _) => val,
+                (Err(_), Arithmetic::BigInt) => {
+                    let val = s.parse::<BigInt>().map_err(|_| malformed(3, "invalid integer"))?;
+                    let wrapped = val.wrapped();
+                    wide.insert(addr, val);
+                    wrapped
+                },
+                _ => return Err(malformed(3, "invalid integer")),
+            };
+            memory.push(val);
+        }
+        let input = parse_list(4)?;
+        let output = parse_list(5)?;
+        let loaded = Vec::from(parse_list(6)?);
+
+        let mut protected = vec![];
+        for s in values[7].split(',').filter(|s| !s.is_empty()) {
+            let parsed = s.split_once(':').and_then(|(range, name)| {
+                let (start, end) = range.split_once("..")?;
+                let protection = match name {
+                    "read-only" => Protection::ReadOnly,
+                    "no-exec"   => Protection::NoExec,
+                    _ => return None,
+                };
+                Some((start.parse::<usize>().ok()?..end.parse::<usize>().ok()?, protection))
+            });
+            protected.push(parsed.ok_or_else(|| malformed(7, "invalid protected range"))?);
+        }
+
+        if memory.is_empty() {
+            return Err(StateError::Invalid("memory is empty".to_owned()));
+        }
+        if instr_ptr >= memory.len() {
+            return Err(StateError::Invalid(format!(
+                "instruction pointer {} is outside of memory", instr_ptr)));
+        }
+        if loaded.len() != memory.len() {
+            return Err(StateError::Invalid("loaded memory is a different size".to_owned()));
+        }
+        for (range, _) in protected.iter() {
+            if range.start > range.end || range.end > memory.len() {
+                return Err(StateError::Invalid(format!(
+                    "protected range {}..{} is not within memory", range.start, range.end)));
+            }
+        }
+
+        let mut prog = Intcode::new(memory).with_arithmetic(arithmetic);
+        prog.wide = wide;
+        prog.loaded = loaded;
+        prog.protected = protected;
+        prog.instr_ptr = instr_ptr;
+        prog.input = input;
+        prog.output = output;
+        prog.status = status;
+
+        Ok(prog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::Arithmetic;
+
+    // adds each input to a running total, which it outputs
+    const TOTAL: &[i64] = &[3, 11, 1, 11, 12, 12, 4, 12, 1105, 1, 0, 0, 0];
+
+    fn save(prog: &Intcode) -> String {
+        let mut buf = vec![];
+        prog.save_state(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn load(text: &str) -> Result<Intcode, StateError> {
+        Intcode::load_state(text.as_bytes())
+    }
+
+    // replaces part of a saved state, with a checksum for the new contents
+    fn edit(text: &str, from: &str, to: &str) -> String {
+        let body = text[..text.rfind("checksum").unwrap()].replacen(from, to, 1);
+        format!("{}checksum {:016x}\n", body, fnv1a(body.as_bytes(), FNV_OFFSET))
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut prog = Intcode::new(TOTAL.to_vec())
+            .with_protection(0..11, Protection::ReadOnly)
+            .with_input(5);
+        prog.run();
+        prog.input(7);
+
+        let mut loaded = load(&save(&prog)).unwrap();
+        assert_eq!(loaded.memory, prog.memory);
+        assert_eq!(loaded.instr_ptr(), prog.instr_ptr());
+        assert_eq!(loaded.status, Status::Waiting);
+        assert_eq!(loaded.protection(), prog.protection());
+        assert_eq!(loaded.modified().collect::<Vec<_>>(), vec![11, 12]);
+
+        // both resume from the same place
+        for p in [&mut prog, &mut loaded].iter_mut() {
+            p.run();
+            assert_eq!(p.output_iter().cloned().collect::<Vec<_>>(), vec![5, 12]);
+        }
+    }
+
+    #[test]
+    fn round_trip_wide_values() {
+        // squares the value at 5
+        let mut prog = Intcode::new(vec![2, 5, 5, 6, 99, 0, 0]).with_arithmetic(Arithmetic::BigInt);
+        prog.set_exact(5, "123456789012345678901234567890".parse().unwrap());
+        prog.step();
+
+        let loaded = load(&save(&prog)).unwrap();
+        assert_eq!(loaded.arithmetic(), Arithmetic::BigInt);
+        assert_eq!(loaded.memory, prog.memory);
+        assert_eq!(loaded.exact(5), prog.exact(5));
+        assert_eq!(loaded.exact(6).to_string(), "15241578753238836750495351562536198787501905199875019052100");
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let mut prog = Intcode::new(TOTAL.to_vec()).with_input(5);
+        prog.run();
+        let text = save(&prog);
+
+        assert!(matches!(load(""), Err(StateError::NotAStateFile)));
+        assert!(matches!(load("1,2,3\n"), Err(StateError::NotAStateFile)));
+        assert!(matches!(
+            load(&text.replacen("intcode-state 1", "intcode-state 2", 1)),
+            Err(StateError::Version(2))));
+        assert!(matches!(
+            load(&text.replacen("memory 3,", "memory 4,", 1)),
+            Err(StateError::Checksum { .. })));
+        assert!(matches!(
+            load(&text.replacen("input \n", "", 1)),
+            Err(StateError::Malformed { .. })));
+
+        // states the VM could not be in
+        let invalid = |text: String| matches!(load(&text), Err(StateError::Invalid(_)));
+        assert!(invalid(edit(&text, "instr_ptr 0", "instr_ptr 13")));
+        assert!(invalid(edit(&text, "loaded 3,", "loaded ")));
+        assert!(invalid(edit(&text, "protection ", "protection 0..14:read-only")));
+        assert!(invalid(edit(&text, "protection ", "protection 8..4:no-exec")));
+        match load(&edit(&text, "protection ", "protection 0..2:read-only,5..99:no-exec")) {
+            Err(err) => assert_eq!(err.to_string(), "invalid state: protected range 5..99 is not within memory"),
+            Ok(_) => panic!("loaded a range outside of memory"),
+        }
+        assert!(load(&edit(&text, "protection ", "protection 0..13:read-only,4..4:no-exec")).is_ok());
+
+        prog.memory[0] = 42;
+        prog.run();
+        assert!(matches!(prog.save_state(vec![]), Err(StateError::Faulted)));
+    }
+}
@@ -0,0 +1,432 @@
+/*
+** src/types/intcode/threaded.rs
+** running connected Intcode programs each on its own thread
+*/
+
+use std::fmt;
+use std::mem;
+use std::panic;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::types::intcode::network::route_output;
+use crate::types::intcode::{Fault, Intcode, IntcodeChain, Packet, Route, Status};
+
+/// Reasons a network of programs stopped before every program halted
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkError {
+    /// a program faulted
+    Faulted { node: usize, fault: Fault },
+    /// every program which had not halted was waiting for input which could
+    /// never arrive
+    Deadlock { waiting: Vec<usize> },
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Faulted { node, fault } => write!(f, "program {} faulted: {}", node, fault),
+            NetworkError::Deadlock { waiting } => {
+                let nodes = waiting.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+                write!(f, "deadlock with programs {} waiting for input", nodes.join(", "))
+            },
+        }
+    }
+}
+
+enum Message {
+    // values sent together are always received together, so that packets
+    // from different programs are never interleaved
+    Values(Vec<i64>),
+    Shutdown,
+}
+
+// the values waiting in an inbox
+fn drain(inbox: &Receiver<Message>) -> Vec<i64> {
+    inbox.try_iter()
+        .flat_map(|msg| match msg {
+            Message::Values(values) => values,
+            Message::Shutdown => vec![],
+        })
+        .collect()
+}
+
+// counts of the programs in each state, used to detect deadlock
+struct State {
+    running: usize,
+    blocked: usize,
+    // messages which have been sent but not yet received
+    in_flight: usize,
+    finished: Vec<bool>,
+    deadlocked: bool,
+    // whether every program has been told to shut down
+    shut_down: bool,
+}
+
+// shared between the threads of a network
+struct Monitor {
+    state: Mutex<State>,
+    inboxes: Vec<Sender<Message>>,
+}
+
+impl Monitor {
+    fn send(&self, to: usize, values: Vec<i64>) {
+        // the message is counted before it is sent so that no thread can see
+        // the network as deadlocked while it is in flight; messages sent to a
+        // finished program are never received, so are not counted
+        let mut state = self.state.lock().unwrap();
+        if !state.finished[to] {
+            state.in_flight += 1;
+        }
+        // inboxes outlive the threads, so sending cannot fail
+        self.inboxes[to].send(Message::Values(values)).unwrap();
+    }
+
+    // Marks a program as finished, returning the values left in its inbox. A
+    // program which faulted shuts down the rest of the network.
+    fn finish(&self, node: usize, inbox: &Receiver<Message>, faulted: bool) -> Vec<i64> {
+        let mut state = self.state.lock().unwrap();
+        let pending = inbox.try_iter()
+            .filter_map(|msg| match msg {
+                Message::Values(values) => Some(values),
+                Message::Shutdown => None,
+            })
+            .collect::<Vec<_>>();
+        state.in_flight -= pending.len();
+        state.finished[node] = true;
+        state.running -= 1;
+        if faulted {
+            self.shut_down(&mut state);
+        }
+        self.check_deadlock(&mut state);
+        pending.concat()
+    }
+
+    // tells every program still running to stop once it next waits for input
+    fn shut_down(&self, state: &mut State) {
+        if !state.shut_down {
+            state.shut_down = true;
+            for inbox in self.inboxes.iter() {
+                let _ = inbox.send(Message::Shutdown);
+            }
+        }
+    }
+
+    // Called whenever a program stops running. If no program is running and
+    // no message is in flight, the waiting programs can never be woken, so
+    // they are shut down.
+    fn check_deadlock(&self, state: &mut State) {
+        if !state.shut_down && state.running == 0 && state.in_flight == 0 && state.blocked > 0 {
+            state.deadlocked = true;
+            self.shut_down(state);
+        }
+    }
+}
+
+// Shuts down the network if a thread panics, such as when its program uses
+// an address outside of memory, so that no other thread waits on it forever
+struct PanicGuard<'a> {
+    node: usize,
+    monitor: &'a Monitor,
+}
+
+impl Drop for PanicGuard<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            // the panic may have poisoned the lock, but the counts are no
+            // longer needed once the network is shut down
+            let mut state = self.monitor.state.lock().unwrap_or_else(|err| err.into_inner());
+            state.finished[self.node] = true;
+            self.monitor.shut_down(&mut state);
+        }
+    }
+}
+
+// What a thread hands back once its program finishes
+struct Node {
+    prog: Intcode,
+    // values left in its inbox when it finished
+    pending: Vec<i64>,
+    inbox: Receiver<Message>,
+    // packets addressed to programs outside the network
+    stray: Vec<Packet>,
+    // output taken from the program, if tapped
+    sent: Option<Vec<i64>>,
+}
+
+// sends a program's output along its route, recording it if tapped
+fn send_output(
+    prog: &mut Intcode, route: &Route, monitor: &Monitor, stray: &mut Vec<Packet>,
+    sent: &mut Option<Vec<i64>>,
+) {
+    let routed = route_output(prog, route, monitor.inboxes.len());
+    for (to, values) in routed.deliveries {
+        monitor.send(to, values);
+    }
+    stray.extend(routed.stray);
+    if let Some(sent) = sent {
+        sent.extend(routed.sent);
+    }
+}
+
+// Runs a single program until it halts, faults or is shut down, blocking on
+// its inbox whenever it waits for input. The inbox is handed back as values
+// may still be sent to it after the program finishes.
+fn run_node(
+    node: usize, mut prog: Intcode, inbox: Receiver<Message>, route: Route, tap: bool,
+    monitor: Arc<Monitor>,
+) -> Node {
+    let _guard = PanicGuard { node, monitor: &monitor };
+    let mut stray = vec![];
+    let mut sent = if tap { Some(vec![]) } else { None };
+    loop {
+        prog.run();
+        send_output(&mut prog, &route, &monitor, &mut stray, &mut sent);
+
+        if prog.status != Status::Waiting {
+            let faulted = matches!(prog.status, Status::Faulted(_));
+            let pending = monitor.finish(node, &inbox, faulted);
+            return Node { prog, pending, inbox, stray, sent };
+        }
+
+        let msg = match inbox.try_recv() {
+            Ok(msg) => msg,
+            Err(TryRecvError::Empty) => {
+                {
+                    let mut state = monitor.state.lock().unwrap();
+                    state.running -= 1;
+                    state.blocked += 1;
+                    monitor.check_deadlock(&mut state);
+                }
+                // the monitor holds a sender for every inbox, so this only
+                // returns once a message arrives
+                let msg = inbox.recv().unwrap();
+                let mut state = monitor.state.lock().unwrap();
+                state.blocked -= 1;
+                state.running += 1;
+                msg
+            },
+            Err(TryRecvError::Disconnected) => unreachable!(),
+        };
+
+        match msg {
+            Message::Values(values) => {
+                monitor.state.lock().unwrap().in_flight -= 1;
+                for val in values {
+                    prog.input(val);
+                }
+            },
+            Message::Shutdown => return Node { prog, pending: vec![], inbox, stray, sent },
+        }
+    }
+}
+
+/// The result of running programs on threads
+pub(super) struct Finished {
+    pub programs: Vec<Intcode>,
+    /// values sent to each program which it never received
+    pub undelivered: Vec<Vec<i64>>,
+    /// packets addressed to programs outside the network
+    pub stray: Vec<Packet>,
+    /// output routed by each program, if tapped
+    pub sent: Vec<Option<Vec<i64>>>,
+    pub deadlocked: bool,
+}
+
+impl Finished {
+    /// the error which stopped the programs, if any; a fault takes precedence
+    /// over the deadlock it may have caused
+    pub fn error(&self) -> Option<NetworkError> {
+        let faulted = self.programs.iter().enumerate().find_map(|(node, prog)| match prog.status {
+            Status::Faulted(ref fault) => Some(NetworkError::Faulted { node, fault: fault.clone() }),
+            _ => None,
+        });
+        if faulted.is_some() {
+            return faulted;
+        }
+        if self.deadlocked {
+            let waiting = self.programs.iter()
+                .enumerate()
+                .filter(|(_, prog)| prog.status == Status::Waiting)
+                .map(|(n, _)| n)
+                .collect();
+            return Some(NetworkError::Deadlock { waiting });
+        }
+        None
+    }
+}
+
+/// Runs each program on its own thread, sending its output along its route,
+/// until every program has halted, one faults or the programs deadlock. If
+/// `tap` is set, the output each program routes is recorded.
+pub(super) fn run_threads(programs: Vec<Intcode>, routes: Vec<Route>, tap: bool) -> Finished {
+    let n_progs = programs.len();
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..n_progs).map(|_| mpsc::channel()).unzip();
+    let monitor = Arc::new(Monitor {
+        state: Mutex::new(State {
+            running: n_progs,
+            blocked: 0,
+            in_flight: 0,
+            finished: vec![false; n_progs],
+            deadlocked: false,
+            shut_down: false,
+        }),
+        inboxes: senders,
+    });
+
+    let handles = programs.into_iter()
+        .zip(receivers)
+        .zip(routes)
+        .enumerate()
+        .map(|(n, ((prog, inbox), route))| {
+            let monitor = monitor.clone();
+            thread::spawn(move || run_node(n, prog, inbox, route, tap, monitor))
+        })
+        .collect::<Vec<_>>();
+
+    // every thread is joined before any inbox is dropped, since a thread may
+    // still be sending to a program which has finished; a panic is passed on
+    // once every thread has stopped
+    let nodes = handles.into_iter()
+        .map(|handle| handle.join())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|err| panic::resume_unwind(err));
+
+    let deadlocked = monitor.state.lock().unwrap().deadlocked;
+    let mut finished = Finished {
+        programs: vec![],
+        undelivered: vec![],
+        stray: vec![],
+        sent: vec![],
+        deadlocked,
+    };
+    for mut node in nodes {
+        node.pending.extend(drain(&node.inbox));
+        finished.programs.push(node.prog);
+        finished.undelivered.push(node.pending);
+        finished.stray.append(&mut node.stray);
+        finished.sent.push(node.sent);
+    }
+    finished
+}
+
+impl IntcodeChain {
+    /// Runs the chain with each program on its own thread, connected to the
+    /// next by a channel; a program waiting for input blocks until its
+    /// predecessor sends a value. Programs shut down as they halt, and the
+    /// chain finishes once every program has halted. If a program faults, or
+    /// every program still running is waiting with no value in flight, the
+    /// remaining programs are shut down and the chain returns an error; a
+    /// panic on any program's thread is passed on once the others have been
+    /// shut down. As with `IntcodeChain::run`, the signal ends up in the final
+    /// program's output, and values not consumed by other programs are left in
+    /// their input.
+    pub fn run_threaded(&mut self) -> Result<(), NetworkError> {
+        let n_progs = self.programs.len();
+        let routes = self.routes();
+        let programs = mem::take(&mut self.programs);
+        let finished = run_threads(programs, routes, self.taps.is_some());
+        let err = finished.error();
+        self.programs = finished.programs;
+        if let Some(ref mut taps) = self.taps {
+            for (tap, sent) in taps.iter_mut().zip(finished.sent) {
+                tap.extend(sent.unwrap_or_default());
+            }
+        }
+
+        // values fed back to the first program after it halted are the
+        // chain's output; any others remain as input
+        for (n, values) in finished.undelivered.into_iter().enumerate() {
+            for val in values {
+                match self.feedback && n == 0 && self.programs[0].status == Status::Halted {
+                    true => self.programs[n_progs - 1].output.push_back(val),
+                    false => self.programs[n].input(val),
+                }
+            }
+        }
+
+        err.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::intcode::Network;
+
+    // day 7: amplifiers connected in a feedback loop
+    const AMPLIFIER_LOOP: &[i64] = &[
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4,
+        27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5,
+    ];
+
+    // outputs each input, forever
+    const ECHO: &[i64] = &[3, 7, 4, 7, 1105, 1, 0, 0];
+
+    fn chain(programs: &[&[i64]]) -> IntcodeChain {
+        IntcodeChain::from(programs.iter().map(|memory| Intcode::new(memory.to_vec())))
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let mut chain = IntcodeChain::from([9, 8, 7, 6, 5].iter()
+            .map(|&phase| Intcode::new(AMPLIFIER_LOOP.to_vec()).with_input(phase)))
+            .with_feedback()
+            .with_taps();
+        chain.input(0);
+        assert_eq!(chain.run_threaded(), Ok(()));
+        assert_eq!(chain.output(), Some(139629729));
+        assert_eq!(chain.tap(4).unwrap().last(), Some(&139629729));
+        assert!(chain.stages().iter().all(|prog| prog.status == Status::Halted));
+    }
+
+    #[test]
+    fn fault_shuts_down() {
+        // the second program executes its input, which is not an opcode
+        let mut chain = chain(&[ECHO, &[3, 2, 0]]).with_feedback();
+        chain.input(42);
+        assert_eq!(chain.run_threaded(), Err(NetworkError::Faulted {
+            node: 1,
+            fault: Fault::UnknownOpcode { instr_ptr: 2, opcode: 42 },
+        }));
+        assert_eq!(chain.stage(0).status, Status::Waiting);
+    }
+
+    #[test]
+    fn deadlock() {
+        let echo_once = &[3, 5, 4, 5, 99, 0];
+        let mut chain = chain(&[echo_once, echo_once]).with_feedback();
+        let err = chain.run_threaded().unwrap_err();
+        assert_eq!(err, NetworkError::Deadlock { waiting: vec![0, 1] });
+        assert_eq!(err.to_string(), "deadlock with programs 0, 1 waiting for input");
+
+        // given input, the chain can be resumed
+        chain.input(1);
+        assert_eq!(chain.run_threaded(), Ok(()));
+        assert_eq!(chain.output(), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds: the len is 5 but the index is 1000")]
+    fn panic_shuts_down() {
+        // the second program reads outside of memory while the first waits
+        // for its output; its panic is passed on unchanged
+        let mut chain = chain(&[&[3, 0, 4, 0, 3, 0, 99], &[3, 0, 4, 1000, 99]]).with_feedback();
+        chain.input(1);
+        let _ = chain.run_threaded();
+    }
+
+    #[test]
+    fn addressed_packets() {
+        let mut network = Network::new()
+            .with_program(Intcode::new(vec![104, 1, 104, 7, 104, 5, 104, 8, 99]))
+            .with_program(Intcode::new(vec![3, 0, 4, 0, 99]))
+            .with_addressing(0, 2);
+        assert_eq!(network.run(), Ok(()));
+        assert_eq!(network.output(1), Some(7));
+        assert_eq!(network.stray_packets(), vec![Packet { addr: 5, values: vec![8] }]);
+    }
+}
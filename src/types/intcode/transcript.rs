@@ -0,0 +1,330 @@
+/*
+** src/types/intcode/transcript.rs
+*/
+
+use std::fmt;
+use std::io::{self, prelude::*, BufReader};
+
+use crate::types::intcode::{Fault, Intcode, Status};
+
+// first line of every transcript file, followed by the format version
+const MAGIC: &str = "intcode-transcript";
+const VERSION: u32 = 1;
+
+/// A single input consumed or output produced by a program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Input(i64),
+    Output(i64),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Input(val)  => write!(f, "< {}", val),
+            Event::Output(val) => write!(f, "> {}", val),
+        }
+    }
+}
+
+/// Errors produced when reading a transcript file
+#[derive(Debug)]
+pub enum TranscriptError {
+    Io(io::Error),
+    NotATranscript,
+    Version(u32),
+    Malformed { line: usize },
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptError::Io(err) => write!(f, "{}", err),
+            TranscriptError::NotATranscript => write!(f, "not an Intcode transcript"),
+            TranscriptError::Version(v) => write!(f, "unsupported transcript version {}", v),
+            TranscriptError::Malformed { line } => write!(f, "line {}: malformed event", line),
+        }
+    }
+}
+
+impl From<io::Error> for TranscriptError {
+    fn from(err: io::Error) -> Self {
+        TranscriptError::Io(err)
+    }
+}
+
+/// What a program did at the point where a replay diverged from its transcript
+#[derive(Clone, Debug, PartialEq)]
+pub enum Observed {
+    Input,           // the program requested input
+    Output(i64),     // the program produced an output
+    Halted,          // the program halted
+    Faulted(Fault),  // the program faulted
+}
+
+/// The first point at which a replayed program diverged from its transcript;
+/// `expected` is None if the transcript had already ended
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Option<Event>,
+    pub actual: Observed,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "event {}: expected ", self.index)?;
+        match self.expected {
+            Some(Event::Input(_)) => write!(f, "an input request")?,
+            Some(Event::Output(val)) => write!(f, "output {}", val)?,
+            None => write!(f, "the end of the transcript")?,
+        }
+        match self.actual {
+            Observed::Input => write!(f, ", program requested input"),
+            Observed::Output(val) => write!(f, ", program output {}", val),
+            Observed::Halted => write!(f, ", program halted"),
+            Observed::Faulted(ref fault) => write!(f, ", program faulted: {}", fault),
+        }
+    }
+}
+
+/// The sequence of inputs consumed and outputs produced by a program, in the
+/// order in which they happened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transcript {
+    events: Vec<Event>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self { events: vec![] }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub(super) fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub(super) fn pop(&mut self) {
+        self.events.pop();
+    }
+
+    /// iterate through the inputs consumed by the program
+    pub fn inputs(&self) -> impl Iterator<Item=i64> + '_ {
+        self.events.iter().filter_map(|event| match event {
+            Event::Input(val) => Some(*val),
+            Event::Output(_) => None,
+        })
+    }
+
+    /// iterate through the outputs produced by the program
+    pub fn outputs(&self) -> impl Iterator<Item=i64> + '_ {
+        self.events.iter().filter_map(|event| match event {
+            Event::Input(_) => None,
+            Event::Output(val) => Some(*val),
+        })
+    }
+
+    /// Writes the transcript as a versioned text file, with one event per
+    /// line: "< n" for an input and "> n" for an output.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{} {}", MAGIC, VERSION)?;
+        for event in self.events.iter() {
+            writeln!(w, "{}", event)?;
+        }
+        w.flush()
+    }
+
+    /// reads a transcript previously written by `Transcript::write`
+    pub fn read<R: Read>(r: R) -> Result<Self, TranscriptError> {
+        let mut lines = BufReader::new(r).lines();
+
+        let header = lines.next().ok_or(TranscriptError::NotATranscript)??;
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version.parse::<u32>()
+                .map_err(|_| TranscriptError::NotATranscript)?,
+            _ => return Err(TranscriptError::NotATranscript),
+        };
+        if version != VERSION {
+            return Err(TranscriptError::Version(version));
+        }
+
+        let mut events = vec![];
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event = line.split_once(' ').and_then(|(dir, val)| {
+                let val = val.trim().parse::<i64>().ok()?;
+                match dir {
+                    "<" => Some(Event::Input(val)),
+                    ">" => Some(Event::Output(val)),
+                    _ => None,
+                }
+            });
+            events.push(event.ok_or(TranscriptError::Malformed { line: n + 2 })?);
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Replays the transcript against a program. Inputs are provided one at a
+    /// time, only when the program blocks on input, so the program sees the
+    /// exact interleaving of inputs and outputs which was recorded. The replay
+    /// succeeds if the program halts or blocks on input at the end of the
+    /// transcript; otherwise returns the first point at which the program
+    /// diverged from the transcript.
+    pub fn replay(&self, prog: &mut Intcode) -> Result<(), Divergence> {
+        let mut events = self.events.iter().enumerate();
+        let diverge = |index: usize, expected: Option<&Event>, actual| Divergence {
+            index,
+            expected: expected.cloned(),
+            actual,
+        };
+
+        loop {
+            prog.run();
+
+            while let Some(out) = prog.output() {
+                match events.next() {
+                    Some((_, Event::Output(val))) if *val == out => {},
+                    Some((index, event)) => {
+                        return Err(diverge(index, Some(event), Observed::Output(out)))
+                    },
+                    None => {
+                        return Err(diverge(self.events.len(), None, Observed::Output(out)))
+                    },
+                }
+            }
+
+            let actual = match prog.status {
+                Status::Waiting => Observed::Input,
+                Status::Halted => Observed::Halted,
+                Status::Faulted(ref fault) => Observed::Faulted(fault.clone()),
+                Status::Initial | Status::Running => unreachable!(),
+            };
+
+            match (events.next(), actual) {
+                (Some((_, Event::Input(val))), Observed::Input) => prog.input(*val),
+                (None, Observed::Halted) | (None, Observed::Input) => return Ok(()),
+                (Some((index, event)), actual) => {
+                    return Err(diverge(index, Some(event), actual))
+                },
+                (None, actual) => {
+                    return Err(diverge(self.events.len(), None, actual))
+                },
+            }
+        }
+    }
+}
+
+impl Intcode {
+    /// Starts recording every input consumed and output produced by the
+    /// program, following the builder pattern.
+    pub fn with_recording(mut self) -> Self {
+        self.transcript = Some(Transcript::new());
+        self
+    }
+
+    /// the events recorded so far, if recording is enabled
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    /// stops recording, returning the events recorded so far
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.transcript.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // adds each input to a running total, which it outputs, halting once the
+    // total is 0
+    const TOTAL: &[i64] = &[3, 13, 1, 13, 14, 14, 4, 14, 1005, 14, 0, 99, 0, 0, 0];
+
+    fn record(input: &[i64]) -> Transcript {
+        let mut prog = Intcode::new(TOTAL.to_vec()).with_recording();
+        for &val in input {
+            prog.input(val);
+        }
+        prog.run();
+        prog.take_transcript().unwrap()
+    }
+
+    #[test]
+    fn records_events() {
+        let transcript = record(&[3, 4, -7]);
+        assert_eq!(transcript.events(), &[
+            Event::Input(3), Event::Output(3),
+            Event::Input(4), Event::Output(7),
+            Event::Input(-7), Event::Output(0),
+        ]);
+        assert_eq!(transcript.inputs().collect::<Vec<_>>(), vec![3, 4, -7]);
+        assert_eq!(transcript.outputs().collect::<Vec<_>>(), vec![3, 7, 0]);
+    }
+
+    #[test]
+    fn write_and_read() {
+        let transcript = record(&[3, 4]);
+        let mut buf = vec![];
+        transcript.write(&mut buf).unwrap();
+        assert_eq!(std::str::from_utf8(&buf).unwrap(), "intcode-transcript 1\n< 3\n> 3\n< 4\n> 7\n");
+        assert_eq!(Transcript::read(&buf[..]).unwrap(), transcript);
+
+        let read = |text: &str| Transcript::read(text.as_bytes());
+        assert!(matches!(read(""), Err(TranscriptError::NotATranscript)));
+        assert!(matches!(read("intcode-transcript 2\n"), Err(TranscriptError::Version(2))));
+        assert!(matches!(
+            read("intcode-transcript 1\n< 3\n\n= 3\n"),
+            Err(TranscriptError::Malformed { line: 4 })));
+        assert!(matches!(read("intcode-transcript 1\n> x\n"), Err(TranscriptError::Malformed { line: 2 })));
+    }
+
+    #[test]
+    fn replays() {
+        let transcript = record(&[3, 4, -7]);
+        let mut prog = Intcode::new(TOTAL.to_vec());
+        assert_eq!(transcript.replay(&mut prog), Ok(()));
+        assert_eq!(prog.status, Status::Halted);
+
+        // a transcript which ends while the program waits for input
+        assert_eq!(record(&[3]).replay(&mut Intcode::new(TOTAL.to_vec())), Ok(()));
+    }
+
+    #[test]
+    fn replay_divergence() {
+        let transcript = record(&[3, 4, -7]);
+
+        // doubles the total instead of adding to it
+        let mut doubled = TOTAL.to_vec();
+        doubled[2] = 2;
+        assert_eq!(transcript.replay(&mut Intcode::new(doubled)), Err(Divergence {
+            index: 1,
+            expected: Some(Event::Output(3)),
+            actual: Observed::Output(0),
+        }));
+
+        // halts straight after its first output
+        let mut halts = TOTAL.to_vec();
+        halts[8] = 99;
+        let err = transcript.replay(&mut Intcode::new(halts)).unwrap_err();
+        assert_eq!(err, Divergence { index: 2, expected: Some(Event::Input(4)), actual: Observed::Halted });
+        assert_eq!(err.to_string(), "event 2: expected an input request, program halted");
+
+        // outputs after the transcript ends
+        let short = Transcript { events: transcript.events()[..1].to_vec() };
+        assert_eq!(short.replay(&mut Intcode::new(TOTAL.to_vec())), Err(Divergence {
+            index: 1,
+            expected: None,
+            actual: Observed::Output(3),
+        }));
+    }
+}
@@ -0,0 +1,446 @@
+/*
+** src/types/intcode/transpile.rs
+** translation of Intcode programs into native Rust
+*/
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+
+use crate::types::intcode::analysis::{DecodeError, Disassembly, Instruction, Target};
+use crate::types::intcode::{OpcodeTable, Param, ParameterMode};
+
+/// Reasons a program cannot be translated; such programs should be run with
+/// the interpreter instead
+#[derive(Clone, Debug, PartialEq)]
+pub enum TranspileError {
+    /// reachable code could not be decoded
+    Decode { addr: usize, err: DecodeError },
+    /// an instruction writes into the program's own code
+    SelfModifying { instr: usize, target: usize },
+    /// an instruction reads or writes a fixed address outside of memory
+    OutOfBounds { instr: usize, addr: i64 },
+}
+
+impl fmt::Display for TranspileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranspileError::Decode { addr, err } => write!(f, "{}: {}", addr, err),
+            TranspileError::SelfModifying { instr, target } => write!(
+                f, "{}: instruction writes into code at {}", instr, target),
+            TranspileError::OutOfBounds { instr, addr } => write!(
+                f, "{}: address {} is outside of memory", instr, addr),
+        }
+    }
+}
+
+// the interface of the generated program, mirroring Intcode
+const PRELUDE: &str = r#"use std::collections::VecDeque;
+
+/// Mirrors the status of the Intcode interpreter. `Fallback` means that
+/// control reached an address which was not translated, or an instruction
+/// was about to write into translated code; the program can be resumed in
+/// the interpreter from `ip` with the current memory and input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Initial,
+    Running,
+    Waiting,
+    Halted,
+    Overflow(usize),
+    Fallback(usize),
+}
+
+pub struct Program {
+    pub memory: Vec<i64>,
+    pub ip: usize,
+    pub input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+    pub status: Status,
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self {
+            memory: MEMORY.to_vec(),
+            ip: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            status: Status::Initial,
+        }
+    }
+
+    /// adds input to the program
+    pub fn input(&mut self, input: i64) {
+        self.input.push_back(input);
+    }
+
+    /// adds input to the program, following the builder pattern
+    pub fn with_input(mut self, input: i64) -> Self {
+        self.input.push_back(input);
+        self
+    }
+
+    /// pops an item from the output queue
+    pub fn output(&mut self) -> Option<i64> {
+        self.output.pop_front()
+    }
+"#;
+
+// checks that a fixed address used by an instruction is inside memory
+fn check_addr(instr: &Instruction, addr: i64, mem_len: usize) -> Result<usize, TranspileError> {
+    if addr >= 0 && (addr as usize) < mem_len {
+        Ok(addr as usize)
+    } else {
+        Err(TranspileError::OutOfBounds { instr: instr.addr, addr })
+    }
+}
+
+// a Rust expression for the value of a read operand; patched operands are
+// read from memory when the instruction executes
+fn read_expr(instr: &Instruction, pn: usize, mem_len: usize) -> Result<String, TranspileError> {
+    let op = &instr.operands[pn];
+    let cell = instr.addr + pn + 1;
+    Ok(match (op.mode, op.patched) {
+        (ParameterMode::Immediate, false) => format!("{}i64", op.value),
+        (ParameterMode::Position, false) => format!("m[{}]", check_addr(instr, op.value, mem_len)?),
+        (ParameterMode::Immediate, true) => format!("m[{}]", cell),
+        (ParameterMode::Position, true) => format!("m[m[{}] as usize]", cell),
+    })
+}
+
+// the address written to by a write operand; for a patched operand this is
+// the variable `dst`, which is loaded before the instruction executes
+fn write_expr(instr: &Instruction, pn: usize, mem_len: usize) -> Result<String, TranspileError> {
+    let op = &instr.operands[pn];
+    if op.patched {
+        Ok("dst".to_string())
+    } else {
+        Ok(check_addr(instr, op.value, mem_len)?.to_string())
+    }
+}
+
+// emits the statements for a single instruction; returns false if control
+// never continues past it
+fn emit_instr(out: &mut String, instr: &Instruction, mem_len: usize) -> Result<bool, TranspileError> {
+    let read = |pn: usize| read_expr(instr, pn, mem_len);
+    let write = |pn: usize| write_expr(instr, pn, mem_len);
+    let pad = "                ";
+
+    writeln!(out, "{}// {}: {}", pad, instr.addr, instr).unwrap();
+    for (pn, op) in instr.operands.iter().enumerate() {
+        if op.kind == Param::Write && op.patched {
+            // a write into translated code would not change what runs, so the
+            // interpreter takes over before it happens
+            writeln!(out, "{}let dst = m[{}] as usize;", pad, instr.addr + pn + 1).unwrap();
+            writeln!(out, "{}if is_code(dst) {{", pad).unwrap();
+            writeln!(out, "{}    self.ip = {};", pad, instr.addr).unwrap();
+            writeln!(out, "{}    self.status = Status::Fallback({});", pad, instr.addr).unwrap();
+            writeln!(out, "{}    return;", pad).unwrap();
+            writeln!(out, "{}}}", pad).unwrap();
+        }
+    }
+    match instr.opcode {
+        1 | 2 => {
+            let method = if instr.opcode == 1 { "checked_add" } else { "checked_mul" };
+            writeln!(out, "{}match i64::{}({}, {}) {{", pad, method, read(0)?, read(1)?).unwrap();
+            writeln!(out, "{}    Some(res) => m[{}] = res,", pad, write(2)?).unwrap();
+            writeln!(out, "{}    None => {{", pad).unwrap();
+            writeln!(out, "{}        self.ip = {};", pad, instr.addr).unwrap();
+            writeln!(out, "{}        self.status = Status::Overflow({});", pad, instr.addr).unwrap();
+            writeln!(out, "{}        return;", pad).unwrap();
+            writeln!(out, "{}    }}", pad).unwrap();
+            writeln!(out, "{}}}", pad).unwrap();
+        },
+        3 => {
+            writeln!(out, "{}match self.input.pop_front() {{", pad).unwrap();
+            writeln!(out, "{}    Some(input) => m[{}] = input,", pad, write(0)?).unwrap();
+            writeln!(out, "{}    None => {{", pad).unwrap();
+            writeln!(out, "{}        self.ip = {};", pad, instr.addr).unwrap();
+            writeln!(out, "{}        self.status = Status::Waiting;", pad).unwrap();
+            writeln!(out, "{}        return;", pad).unwrap();
+            writeln!(out, "{}    }}", pad).unwrap();
+            writeln!(out, "{}}}", pad).unwrap();
+        },
+        4 => {
+            writeln!(out, "{}self.output.push_back({});", pad, read(0)?).unwrap();
+        },
+        5 | 6 => {
+            let target = match instr.jump_target() {
+                Some(Target::Static(addr)) => addr.to_string(),
+                Some(Target::Dynamic) => format!("{} as usize", read(1)?),
+                // the jump is never taken
+                None => return Ok(true),
+            };
+            if !instr.falls_through() {
+                writeln!(out, "{}self.ip = {};", pad, target).unwrap();
+                writeln!(out, "{}continue;", pad).unwrap();
+                return Ok(false);
+            }
+            let cmp = if instr.opcode == 5 { "!=" } else { "==" };
+            writeln!(out, "{}if {} {} 0 {{", pad, read(0)?, cmp).unwrap();
+            writeln!(out, "{}    self.ip = {};", pad, target).unwrap();
+            writeln!(out, "{}    continue;", pad).unwrap();
+            writeln!(out, "{}}}", pad).unwrap();
+        },
+        7 | 8 => {
+            let cmp = if instr.opcode == 7 { "<" } else { "==" };
+            writeln!(out, "{}m[{}] = ({} {} {}) as i64;", pad, write(2)?, read(0)?, cmp, read(1)?)
+                .unwrap();
+        },
+        99 => {
+            writeln!(out, "{}self.ip = {};", pad, instr.addr).unwrap();
+            writeln!(out, "{}self.status = Status::Halted;", pad).unwrap();
+            writeln!(out, "{}return;", pad).unwrap();
+            return Ok(false);
+        },
+        _ => unreachable!("only standard opcodes are decoded"),
+    }
+
+    Ok(true)
+}
+
+// whether or not a disassembly can be translated: every reachable address
+// decodes and no executed instruction word is overwritten
+fn is_clean(disasm: &Disassembly) -> bool {
+    disasm.errors.is_empty() && disasm.live_opcode_writes().is_empty()
+}
+
+/// Translates a program into the source of a Rust module exposing a `Program`
+/// with the same input/output interface as `Intcode`. Each basic block of the
+/// program becomes an arm of a `match` on the instruction pointer, and
+/// operands which the program overwrites are read from memory when used.
+/// Programs which overwrite their own instructions are refused, since their
+/// code cannot be known ahead of time. Jumps to an address which was not
+/// translated, and writes to a computed address inside the translated code,
+/// stop the program with `Status::Fallback` so that it can be resumed in the
+/// interpreter. The translation assumes the program starts from its initial
+/// memory image.
+pub fn transpile(memory: &[i64]) -> Result<String, TranspileError> {
+    let disasm = Disassembly::explore(memory, &OpcodeTable::standard(), is_clean);
+
+    // a word which cannot be decoded until it is written is reported as the
+    // write, which is the reason it cannot be translated
+    if let Some(&(instr, target)) = disasm.live_opcode_writes().first() {
+        return Err(TranspileError::SelfModifying { instr, target });
+    }
+    if let Some((&addr, &err)) = disasm.errors.iter().next() {
+        return Err(TranspileError::Decode { addr, err });
+    }
+
+    // input instructions also start blocks, so that the program can resume
+    // at them after waiting
+    let mut leaders = disasm.leaders();
+    leaders.extend(disasm.instrs.values().filter(|i| i.opcode == 3).map(|i| i.addr));
+
+    let mut out = String::new();
+    writeln!(out, "// generated from a {}-word Intcode program; do not edit", memory.len()).unwrap();
+    writeln!(out).unwrap();
+    out.push_str(PRELUDE);
+
+    writeln!(out).unwrap();
+    writeln!(out, "    pub fn run(&mut self) {{").unwrap();
+    writeln!(out, "        let m = &mut self.memory;").unwrap();
+    writeln!(out, "        self.status = Status::Running;").unwrap();
+    writeln!(out, "        loop {{").unwrap();
+    writeln!(out, "            match self.ip {{").unwrap();
+
+    for &leader in leaders.iter() {
+        writeln!(out, "            {} => {{", leader).unwrap();
+
+        let mut addr = leader;
+        loop {
+            let instr = &disasm.instrs[&addr];
+            if !emit_instr(&mut out, instr, memory.len())? {
+                break;
+            }
+
+            // the block ends at the next leader, or where control falls
+            // through into code which was not reached by the disassembler
+            addr = instr.next_addr();
+            if leaders.contains(&addr) || !disasm.instrs.contains_key(&addr) {
+                writeln!(out, "                self.ip = {};", addr).unwrap();
+                break;
+            }
+        }
+
+        writeln!(out, "            }},").unwrap();
+    }
+
+    writeln!(out, "            ip => {{").unwrap();
+    writeln!(out, "                self.status = Status::Fallback(ip);").unwrap();
+    writeln!(out, "                return;").unwrap();
+    writeln!(out, "            }},").unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    // cells whose values were translated into code, which the program must
+    // not write to at runtime; patched operands are read from memory
+    if !disasm.dynamic_writes().is_empty() {
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        let cells = disasm.instrs.values()
+            .flat_map(|instr| instr.cells())
+            .filter(|cell| !disasm.patched.contains(cell))
+            .collect::<BTreeSet<_>>();
+        for cell in cells {
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == cell => range.1 = cell,
+                _ => ranges.push((cell, cell)),
+            }
+        }
+        let arms = ranges.iter()
+            .map(|&(lo, hi)| if lo == hi { lo.to_string() } else { format!("{}..={}", lo, hi) })
+            .collect::<Vec<_>>();
+
+        writeln!(out).unwrap();
+        writeln!(out, "fn is_code(addr: usize) -> bool {{").unwrap();
+        writeln!(out, "    matches!(addr, {})", arms.join(" | ")).unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    // the initial memory image
+    writeln!(out).unwrap();
+    writeln!(out, "const MEMORY: [i64; {}] = [", memory.len()).unwrap();
+    for chunk in memory.chunks(12) {
+        let line = chunk.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+        writeln!(out, "    {},", line).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::iter;
+    use std::process::Command;
+
+    use crate::types::intcode::{Intcode, Status};
+
+    // outputs 999 if the input is below 8, 1000 if it is 8 and 1001 if it is
+    // above 8
+    const COMPARE_TO_8: &[i64] = &[
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31,
+        1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104,
+        999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99,
+    ];
+
+    // counts down from the input, outputting each value
+    const COUNTDOWN: &[i64] = &[
+        1101, 0, 21, 20, 3, 21, 4, 21, 1001, 21, -1, 21, 1005, 21, 6, 99, 0, 0, 0, 0, 0, 0,
+    ];
+
+    // computes the address of a write into data
+    const COMPUTED_WRITE: &[i64] = &[1101, 0, 12, 7, 1101, 5, 5, 0, 4, 12, 99, 0, 0];
+
+    // writes 99 over its own output instruction through a computed address
+    const OVERWRITES_CODE: &[i64] = &[1101, 0, 12, 7, 1101, 1, 98, 0, 1105, 1, 12, 0, 104, 5, 99];
+
+    // runs a program in the interpreter, resuming from where the translated
+    // program stopped if it fell back to the interpreter
+    fn interpret(memory: &[i64], ip: usize, input: &[i64]) -> (Vec<i64>, Vec<i64>) {
+        let mut prog = Intcode::new(memory.to_vec()).with_instr_ptr(ip);
+        for &val in input {
+            prog.input(val);
+        }
+        prog.run();
+        assert_eq!(prog.status, Status::Halted);
+        (iter::from_fn(|| prog.output()).collect(), prog.memory)
+    }
+
+    // Translates each program, then compiles and runs them together in a
+    // single binary, returning the lines it printed: the status, output and
+    // memory of each run.
+    fn run_native(cases: &[(&[i64], &[i64])]) -> Vec<String> {
+        let mut source = String::new();
+        let mut main = String::from("fn main() {\n");
+        for (n, (memory, input)) in cases.iter().enumerate() {
+            let module = transpile(memory).unwrap();
+            source += &format!("#[allow(dead_code)]\nmod p{} {{\n{}}}\n", n, module);
+            main += &format!("    let mut prog = p{}::Program::new();\n", n);
+            for val in input.iter() {
+                main += &format!("    prog.input({});\n", val);
+            }
+            main += "    prog.run();\n";
+            main += "    let output = prog.output.iter().cloned().collect::<Vec<_>>();\n";
+            main += "    println!(\"{:?} {:?} {:?}\", prog.status, output, prog.memory);\n";
+        }
+        source += &main;
+        source += "}\n";
+
+        let dir = env::temp_dir().join(format!("intcode-transpile-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("main.rs");
+        let bin = dir.join("main");
+        fs::write(&src, source).unwrap();
+
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+        let status = Command::new(rustc)
+            .args(["--edition", "2018", "-o"])
+            .arg(&bin)
+            .arg(&src)
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated code failed to compile");
+
+        let output = Command::new(&bin).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap().lines().map(|l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn generated_code_matches_interpreter() {
+        let cases: &[(&[i64], &[i64])] = &[
+            (COMPARE_TO_8, &[7]),
+            (COMPARE_TO_8, &[8]),
+            (COMPARE_TO_8, &[9]),
+            (COUNTDOWN, &[3]),
+            (COMPUTED_WRITE, &[]),
+            (OVERWRITES_CODE, &[]),
+        ];
+        let lines = run_native(cases);
+        assert_eq!(lines.len(), cases.len());
+
+        for (line, (memory, input)) in lines.iter().zip(cases.iter()).take(5) {
+            let (output, final_memory) = interpret(memory, 0, input);
+            assert_eq!(*line, format!("Halted {:?} {:?}", output, final_memory));
+        }
+
+        // the write into code is left to the interpreter, which then halts
+        // without any output, as it does when run from the start
+        let mut memory = OVERWRITES_CODE.to_vec();
+        memory[7] = 12;
+        assert_eq!(lines[5], format!("Fallback(4) [] {:?}", memory));
+        assert_eq!(interpret(&memory, 4, &[]), interpret(OVERWRITES_CODE, 0, &[]));
+        assert_eq!(interpret(OVERWRITES_CODE, 0, &[]).0, vec![]);
+    }
+
+    #[test]
+    fn refuses_self_modifying_code() {
+        // the opcode at 6 cannot be decoded until the input is added to it
+        let err = transpile(&[3, 11, 1, 11, 6, 6, 1100, 1, 2, 0, 99, 0]).unwrap_err();
+        assert_eq!(err, TranspileError::SelfModifying { instr: 2, target: 6 });
+
+        let err = transpile(&[1101, 0, 99, 4, 1, 0, 0, 0]).unwrap_err();
+        assert_eq!(err, TranspileError::SelfModifying { instr: 0, target: 4 });
+        assert_eq!(transpile(&[1, 0, 0, 0, 99]).map(|_| ()), Ok(()));
+
+        let err = transpile(&[1101, 1, 1, 100, 99]).unwrap_err();
+        assert_eq!(err, TranspileError::OutOfBounds { instr: 0, addr: 100 });
+        assert_eq!(transpile(&[42]).unwrap_err(), TranspileError::Decode {
+            addr: 0,
+            err: DecodeError::UnknownOpcode(42),
+        });
+    }
+}
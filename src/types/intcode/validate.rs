@@ -0,0 +1,232 @@
+/*
+** src/types/intcode/validate.rs
+** static checks run on a program before it is executed
+*/
+
+use std::fmt;
+
+use crate::types::intcode::analysis::{DecodeError, Disassembly, Target};
+use crate::types::intcode::{Intcode, OpcodeTable, Param, ParameterMode};
+
+/// How serious a problem is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// the program faults or panics if execution reaches the problem
+    Error,
+    /// the program may run, but likely not as intended
+    Warning,
+}
+
+/// A problem found in reachable code
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Problem {
+    /// control reaches a word which is not a valid instruction
+    Undecodable(DecodeError),
+    /// control leaves memory, through a jump or by running off the end
+    ControlOutOfBounds { target: i64 },
+    /// a write parameter in immediate mode, which the VM treats as an address
+    ImmediateWrite { param: usize },
+    /// a position-mode read from a constant address outside of memory
+    ReadOutOfBounds { param: usize, addr: i64 },
+    /// a write to a constant address outside of memory
+    WriteOutOfBounds { param: usize, addr: i64 },
+    /// a jump to a computed address; code reached only through it is not
+    /// checked
+    DynamicJump,
+    /// an undecodable word which the instruction at the given address writes
+    /// to, so it may be valid by the time it runs
+    Overwritten { by: usize },
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::ImmediateWrite { .. } | Problem::DynamicJump | Problem::Overwritten { .. } => {
+                Severity::Warning
+            },
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Undecodable(err) => write!(f, "{}", err),
+            Problem::ControlOutOfBounds { target } => {
+                write!(f, "control leaves memory at address {}", target)
+            },
+            Problem::ImmediateWrite { param } => {
+                write!(f, "parameter {} is written to in immediate mode", param + 1)
+            },
+            Problem::ReadOutOfBounds { param, addr } => {
+                write!(f, "parameter {} reads from address {} outside of memory", param + 1, addr)
+            },
+            Problem::WriteOutOfBounds { param, addr } => {
+                write!(f, "parameter {} writes to address {} outside of memory", param + 1, addr)
+            },
+            Problem::DynamicJump => write!(f, "jump to a computed address is not followed"),
+            Problem::Overwritten { by } => {
+                write!(f, "undecodable word is written at runtime by the instruction at {}", by)
+            },
+        }
+    }
+}
+
+/// A problem along with the address of the instruction it was found in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub addr: usize,
+    pub problem: Problem,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.problem.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", self.addr, severity, self.problem)
+    }
+}
+
+/// Checks the code reachable from address 0 without running it, returning
+/// every problem found in order of address. Operands the program overwrites
+/// are only known at runtime, so are not checked against memory bounds.
+pub fn validate(memory: &[i64], opcodes: &OpcodeTable) -> Vec<Diagnostic> {
+    let disasm = Disassembly::new(memory, opcodes);
+    let in_bounds = |addr: i64| addr >= 0 && (addr as usize) < memory.len();
+    let mut diags = vec![];
+
+    for (&addr, instr) in disasm.instrs.iter() {
+        for (param, op) in instr.operands.iter().enumerate() {
+            if op.kind == Param::Write && op.mode == ParameterMode::Immediate {
+                diags.push(Diagnostic { addr, problem: Problem::ImmediateWrite { param } });
+            }
+            match (op.read_addr(), op.write_addr()) {
+                (Some(a), _) if !in_bounds(a) => {
+                    diags.push(Diagnostic { addr, problem: Problem::ReadOutOfBounds { param, addr: a } });
+                },
+                (_, Some(a)) if !in_bounds(a) => {
+                    diags.push(Diagnostic { addr, problem: Problem::WriteOutOfBounds { param, addr: a } });
+                },
+                _ => (),
+            }
+        }
+
+        for target in instr.successors() {
+            match target {
+                Target::Static(next) if next >= memory.len() => diags.push(Diagnostic {
+                    addr,
+                    problem: Problem::ControlOutOfBounds { target: next as i64 },
+                }),
+                Target::Dynamic => diags.push(Diagnostic { addr, problem: Problem::DynamicJump }),
+                _ => (),
+            }
+        }
+    }
+
+    // leaving memory is reported at the instruction which does so, above;
+    // a word which the program writes to may be valid once it runs
+    for (&addr, &err) in disasm.errors.iter() {
+        let writer = disasm.static_writes().find(|&(_, target)| target == addr);
+        match (err, writer) {
+            (DecodeError::OutOfBounds, _) => (),
+            (_, Some((by, _))) => diags.push(Diagnostic { addr, problem: Problem::Overwritten { by } }),
+            _ => diags.push(Diagnostic { addr, problem: Problem::Undecodable(err) }),
+        }
+    }
+
+    diags.sort_by_key(|diag| diag.addr);
+    diags
+}
+
+impl Intcode {
+    /// checks the program's current memory with its own opcodes, as with
+    /// `validate`
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate(&self.memory, &self.opcodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(memory: &[i64]) -> Vec<Diagnostic> {
+        validate(memory, &OpcodeTable::standard())
+    }
+
+    fn at(addr: usize, problem: Problem) -> Diagnostic {
+        Diagnostic { addr, problem }
+    }
+
+    #[test]
+    fn valid_program() {
+        assert_eq!(check(&[3, 0, 4, 0, 99]), vec![]);
+        assert_eq!(Intcode::new(vec![3, 0, 4, 0, 99]).validate(), vec![]);
+    }
+
+    #[test]
+    fn operand_problems() {
+        let diags = check(&[11101, 1, 1, 4, 99]);
+        assert_eq!(diags, vec![at(0, Problem::ImmediateWrite { param: 2 })]);
+        assert_eq!(diags[0].severity(), Severity::Warning);
+        assert_eq!(diags[0].to_string(), "0: warning: parameter 3 is written to in immediate mode");
+
+        let diags = check(&[4, 10, 3, -1, 99]);
+        assert_eq!(diags, vec![
+            at(0, Problem::ReadOutOfBounds { param: 0, addr: 10 }),
+            at(2, Problem::WriteOutOfBounds { param: 0, addr: -1 }),
+        ]);
+        assert_eq!(diags[0].to_string(), "0: error: parameter 1 reads from address 10 outside of memory");
+        assert_eq!(diags[1].to_string(), "2: error: parameter 1 writes to address -1 outside of memory");
+
+        // operands written at runtime are not checked
+        assert_eq!(check(&[3, 3, 4, 99, 99]), vec![]);
+    }
+
+    #[test]
+    fn control_problems() {
+        let diags = check(&[104, 1]);
+        assert_eq!(diags, vec![at(0, Problem::ControlOutOfBounds { target: 2 })]);
+        assert_eq!(diags[0].to_string(), "0: error: control leaves memory at address 2");
+        assert_eq!(check(&[1105, 1, 50]), vec![at(0, Problem::ControlOutOfBounds { target: 50 })]);
+
+        // the jump target is read from input
+        let diags = check(&[3, 4, 1105, 1, 0, 99]);
+        assert_eq!(diags, vec![at(2, Problem::DynamicJump)]);
+        assert_eq!(diags[0].severity(), Severity::Warning);
+        assert_eq!(diags[0].to_string(), "2: warning: jump to a computed address is not followed");
+    }
+
+    #[test]
+    fn undecodable_words() {
+        let diags = check(&[1105, 1, 4, 99, 42]);
+        assert_eq!(diags, vec![at(4, Problem::Undecodable(DecodeError::UnknownOpcode(42)))]);
+        assert_eq!(diags[0].to_string(), "4: error: unknown opcode 42");
+        assert_eq!(check(&[1, 0]), vec![at(0, Problem::Undecodable(DecodeError::Truncated))]);
+
+        // the add stores 99 over the word before it runs
+        let diags = check(&[1101, 1, 98, 4, 42]);
+        assert_eq!(diags, vec![at(4, Problem::Overwritten { by: 0 })]);
+        assert_eq!(diags[0].to_string(), "4: warning: undecodable word is written at runtime by the instruction at 0");
+    }
+
+    #[test]
+    fn validates_day_5() {
+        let prog = Intcode::new(Intcode::parse(crate::utils::PuzzleInput::new(5).next().unwrap()));
+        let diags = prog.validate();
+        assert_eq!(diags[0], at(6, Problem::Overwritten { by: 2 }));
+        assert_eq!(diags.iter().filter(|diag| diag.severity() == Severity::Error).count(), 4);
+        assert!(diags.iter()
+            .filter(|diag| diag.severity() == Severity::Error)
+            .all(|diag| diag.problem == Problem::ControlOutOfBounds { target: 99999 }));
+    }
+}
@@ -30,7 +30,7 @@ impl Password {
             dcnt[d as usize] += 1;
         }
 
-        dcnt.iter().any(|&dcnt| dcnt == 2)
+        dcnt.contains(&2)
     }
 
     // increment with wrap-around
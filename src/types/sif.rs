@@ -37,13 +37,8 @@ impl SIFImage {
             .map(|layer| layer[pixn])
             .find(|&pixel| pixel != 2);
 
-        if let Some(pixel) = pixel {
-            // non-transparent pixel found
-            pixel
-        } else {
-            // otherwise all layers are transparent
-            2
-        }
+        // if there is none, all layers are transparent
+        pixel.unwrap_or(2)
     }
 
     pub fn render(&self) -> SIFLayer {
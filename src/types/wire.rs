@@ -28,8 +28,7 @@ impl WireSegment {
             .map(|p| (p, p.manhattan_distance()))
             .collect::<Vec<(Point, i32)>>();
 
-        endpoint_dists
-            .sort_by(|(_, p1_dist), (_, p2_dist)| p1_dist.cmp(p2_dist));
+        endpoint_dists.sort_by_key(|&(_, dist)| dist);
 
         endpoint_dists[1].0
     }
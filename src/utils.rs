@@ -31,10 +31,7 @@ impl Iterator for PuzzleInput {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.inner.next() {
-            Some(line) => Some(line.unwrap()),
-            None => None,
-        }
+        self.inner.next().map(|line| line.unwrap())
     }
 }
 
@@ -56,14 +53,12 @@ where I: Iterator<Item=S>,
     type Item = i64;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.inner.next() {
-            Some(s) => Some(s.into().parse::<i64>().unwrap()),
-            None => None
-        }
+        self.inner.next().map(|s| s.into().parse::<i64>().unwrap())
     }
 }
 
 // iterator extension for ParseIntIter
+#[allow(clippy::wrong_self_convention)]
 pub trait ParseIntIterExt: Iterator
 where Self: Sized
 {